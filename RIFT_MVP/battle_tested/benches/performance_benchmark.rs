@@ -0,0 +1,37 @@
+use std::hint::black_box;
+
+use battle_tested::{MinimalParser, Parser, ParserConfig};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn bench_classification_cache(c: &mut Criterion) {
+    let input = "if total > limit { total = total - limit ; count = count + 1 }\n".repeat(2_000);
+    let uncached = MinimalParser::new();
+    let cached = MinimalParser::with_config(ParserConfig {
+        classification_cache_size: Some(32),
+        ..ParserConfig::default()
+    });
+
+    let mut group = c.benchmark_group("repetitive_input");
+    group.bench_function("uncached", |b| b.iter(|| uncached.parse(black_box(&input))));
+    group.bench_function("cached", |b| b.iter(|| cached.parse(black_box(&input))));
+    group.finish();
+}
+
+fn bench_position_tracking(c: &mut Criterion) {
+    let input = "let naïve = \"välue\" + count * 2 ; // résumé\n".repeat(2_000);
+    let tracked = MinimalParser::new();
+    let untracked = MinimalParser::with_config(ParserConfig {
+        track_positions: false,
+        ..ParserConfig::default()
+    });
+
+    let mut group = c.benchmark_group("position_tracking");
+    group.bench_function("tracked", |b| b.iter(|| tracked.parse(black_box(&input))));
+    group.bench_function("untracked", |b| {
+        b.iter(|| untracked.parse(black_box(&input)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_classification_cache, bench_position_tracking);
+criterion_main!(benches);
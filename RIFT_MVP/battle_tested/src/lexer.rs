@@ -0,0 +1,1184 @@
+// Character-Level Lexer
+//
+// Turns source text into tokens one line at a time, walking each line a
+// character at a time. `MinimalParser` drives a `Lexer` for all of its
+// parsing entry points; this module holds the scanner and its helpers.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
+use crate::{
+    Classifier, DelimitedMode, ParserConfig, ParserError, ScanMetrics, Span, Token, TokenType,
+};
+
+// Least-recently-used memo of word classifications. Eviction scans for the
+// oldest entry, which is cheap for the small capacities this is meant for.
+struct ClassificationCache {
+    capacity: usize,
+    entries: HashMap<String, (TokenType, u64)>,
+    tick: u64,
+}
+
+impl ClassificationCache {
+    fn new(capacity: usize) -> Self {
+        ClassificationCache {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, word: &str) -> Option<TokenType> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(word).map(|(kind, used)| {
+            *used = tick;
+            kind.clone()
+        })
+    }
+
+    fn insert(&mut self, word: &str, kind: TokenType) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(word, _)| word.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(word.to_string(), (kind, self.tick));
+    }
+}
+
+// Decodes the string literal opening at `start`, returning its content
+// without quotes and the byte offset just past the closing quote, or `None`
+// if the line ends first. The literal is closed by the same character that
+// opened it.
+fn decode_string(line: &str, start: usize) -> Option<(String, usize)> {
+    let quote = line[start..].chars().next()?;
+    let body = start + quote.len_utf8();
+    let mut value = String::new();
+    let mut chars = line[body..].char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            _ if c == quote => return Some((value, body + i + c.len_utf8())),
+            '\\' => match chars.next().map(|(_, e)| e) {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some('0') => value.push('\0'),
+                Some(e) if e == '\\' || e == quote => value.push(e),
+                // Line continuation, only seen when a continued string's lines
+                // are decoded together.
+                Some('\n') => {}
+                Some(e) => {
+                    value.push('\\');
+                    value.push(e);
+                }
+                None => break,
+            },
+            _ => value.push(c),
+        }
+    }
+
+    None
+}
+
+// True when unclosed string text ends in an unescaped backslash, i.e. an
+// odd run of them, which continues the string on the next line.
+fn continues_string(text: &str) -> bool {
+    text.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+// Reusable Lexer
+//
+// Scans each line a character at a time (see `lex_line`), so tokens need no
+// whitespace between them. Accumulates tokens across `lex` calls so a
+// single instance can be pooled: `drain_tokens` hands back the tokens and
+// resets the per-input state.
+pub struct Lexer<'a> {
+    config: &'a ParserConfig,
+    pub(crate) tokens: Vec<Token>,
+    pub(crate) line: usize,
+    cache: Option<ClassificationCache>,
+    // Byte offset of the line being lexed, and of the first byte not yet
+    // turned into a token, both relative to the current `lex` input.
+    pub(crate) line_offset: usize,
+    consumed: usize,
+    // Longest registered operator in bytes; bounds each longest-match probe.
+    max_operator_len: usize,
+    pub(crate) heredoc: Option<PendingBlock>,
+    // String literal continued past the end of its line; `body` holds its
+    // source lines so far, opening quote included.
+    string: Option<PendingBlock>,
+    // Line break that ended the last line, as spelled in the source.
+    line_break: String,
+    pub(crate) doc_block: Option<PendingBlock>,
+    pub(crate) scans: ScanMetrics,
+    // Replaces `config.classify` for words when set.
+    classifier: Option<&'a dyn Classifier>,
+}
+
+// A multi-line token (heredoc, block doc comment or continued string) whose
+// opening marker has been seen but whose terminator has not.
+pub(crate) struct PendingBlock {
+    terminator: String,
+    body: Vec<String>,
+    // Source spelling so far, from the opening marker on and with the line
+    // breaks as they appear in the input. Unused for heredocs.
+    source: String,
+    line: usize,
+    column: usize,
+    start: usize,
+    // Position in `tokens` the finished token belongs at, so it precedes
+    // anything lexed after the marker on the same line.
+    index: usize,
+}
+
+// Lossless-mode bookkeeping: turns the source between lexed tokens into
+// tokens of its own and gives lexed tokens their source spelling.
+// Every call takes the same `input` the tokens were lexed from.
+pub(crate) struct GapFiller {
+    track_positions: bool,
+    // Offset just past the last byte covered by a token.
+    end: usize,
+    // Byte offset, line and column of the last gap start. Gaps only move
+    // forward, so each position is counted on from the previous one.
+    cursor: (usize, usize, usize),
+}
+
+impl GapFiller {
+    pub(crate) fn new(config: &ParserConfig) -> Self {
+        GapFiller {
+            track_positions: config.track_positions,
+            end: 0,
+            cursor: (0, 1, 1),
+        }
+    }
+
+    pub(crate) fn push(&mut self, input: &str, mut token: Token, out: &mut VecDeque<Token>) {
+        self.fill_to(input, token.span.start, out);
+        if let Some(source) = input.get(token.span.start..token.span.end) {
+            token.value = source.to_string();
+            token.raw = None;
+        }
+        self.end = self.end.max(token.span.end);
+        out.push_back(token);
+    }
+
+    // Emits the text from the last token up to `until`, one token per run
+    // of line breaks, of other whitespace, or of anything else.
+    pub(crate) fn fill_to(&mut self, input: &str, until: usize, out: &mut VecDeque<Token>) {
+        let class = |c: char| match c {
+            '\n' | '\r' => 0,
+            c if c.is_whitespace() => 1,
+            _ => 2,
+        };
+        while self.end < until {
+            let gap = &input[self.end..until];
+            let first = gap.chars().next().map_or(2, class);
+            let len = gap.find(|c| class(c) != first).unwrap_or(gap.len());
+            let (line, column) = if self.track_positions {
+                self.position(input, self.end)
+            } else {
+                (0, 0)
+            };
+            out.push_back(Token {
+                kind: if first == 2 {
+                    TokenType::Unknown
+                } else {
+                    TokenType::Whitespace
+                },
+                value: gap[..len].to_string(),
+                line,
+                column,
+                raw: None,
+                span: Span::new(self.end, self.end + len),
+                payload: (),
+            });
+            self.end += len;
+        }
+    }
+
+    // Line and column of byte `offset`, which is never before the cursor.
+    // Line breaks are counted as `SourceBuffer` counts them.
+    fn position(&mut self, input: &str, offset: usize) -> (usize, usize) {
+        let (start, mut line, mut column) = self.cursor;
+        let text = &input[start..offset];
+        for (i, c) in text.char_indices() {
+            if c == '\n' || (c == '\r' && !input[start + i + 1..].starts_with('\n')) {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        self.cursor = (offset, line, column);
+        (line, column)
+    }
+}
+
+// `input` with every `\r\n` and lone `\r` turned into `\n`. Borrowed when
+// there is no `\r` to rewrite.
+pub fn normalize_newlines(input: &str) -> Cow<'_, str> {
+    if !input.contains('\r') {
+        return Cow::Borrowed(input);
+    }
+    Cow::Owned(input.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+// Each line of `input` with the byte offset it starts at and the line
+// break ending it. Lines end at `\n`, `\r\n` or a lone `\r`; the last
+// line's break is empty when the input does not end in one.
+pub(crate) fn lines_with_offsets(input: &str) -> impl Iterator<Item = (usize, &str, &str)> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let start = offset;
+        let (line, line_break) = line_at(input, start)?;
+        offset += line.len() + line_break.len();
+        Some((start, line, line_break))
+    })
+}
+
+// The line of `input` starting at byte `start` and the line break ending
+// it, or `None` at the end of `input`.
+pub(crate) fn line_at(input: &str, start: usize) -> Option<(&str, &str)> {
+    let rest = input.get(start..).filter(|rest| !rest.is_empty())?;
+    let (len, terminator) = match rest.find(['\n', '\r']) {
+        Some(i) if rest[i..].starts_with("\r\n") => (i, 2),
+        Some(i) => (i, 1),
+        None => (rest.len(), 0),
+    };
+    Some((&rest[..len], &rest[len..len + terminator]))
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(config: &'a ParserConfig) -> Self {
+        Lexer {
+            config,
+            tokens: Vec::new(),
+            line: 0,
+            cache: config
+                .classification_cache_size
+                .map(ClassificationCache::new),
+            line_offset: 0,
+            consumed: 0,
+            max_operator_len: config.max_operator_len(),
+            heredoc: None,
+            string: None,
+            line_break: String::new(),
+            doc_block: None,
+            scans: ScanMetrics::default(),
+            classifier: None,
+        }
+    }
+
+    // Lexer that asks `classifier` for the kind of each word instead of
+    // `config`. Everything else, including operator and string matching,
+    // still follows `config`.
+    pub fn with_classifier(config: &'a ParserConfig, classifier: &'a dyn Classifier) -> Self {
+        Lexer {
+            classifier: Some(classifier),
+            ..Lexer::new(config)
+        }
+    }
+
+    // Swaps the configuration for subsequent `lex` calls. Cached
+    // classifications were computed against the old config, so they are dropped.
+    pub fn set_config(&mut self, config: &'a ParserConfig) {
+        self.config = config;
+        self.cache = config
+            .classification_cache_size
+            .map(ClassificationCache::new);
+        self.max_operator_len = config.max_operator_len();
+    }
+
+    // Longest-match operator lookup at the start of `text`. Only prefixes up
+    // to `max_operator_len` bytes are tried, so each position costs a bounded
+    // number of probes and the scanner never backtracks beyond that window.
+    fn match_operator(&self, text: &str) -> Option<usize> {
+        let longest = self.max_operator_len.min(text.len());
+        (1..=longest)
+            .rev()
+            .find(|&len| text.is_char_boundary(len) && self.config.operators.contains(&text[..len]))
+    }
+
+    fn classify(&mut self, word: &str) -> TokenType {
+        let classifier = self.classifier.unwrap_or(self.config);
+        let Some(cache) = self.cache.as_mut() else {
+            return classifier.classify(word);
+        };
+        if let Some(kind) = cache.get(word) {
+            return kind;
+        }
+        let kind = classifier.classify(word);
+        cache.insert(word, kind.clone());
+        kind
+    }
+
+    // `input` as it is lexed: with `normalize_newlines`, its line endings
+    // rewritten to `\n`. Every entry point lexes this rather than the raw
+    // input, so they all agree on spans.
+    pub(crate) fn source<'i>(&self, input: &'i str) -> Cow<'i, str> {
+        if self.config.normalize_newlines {
+            normalize_newlines(input)
+        } else {
+            Cow::Borrowed(input)
+        }
+    }
+
+    pub fn lex(&mut self, input: &str) -> Result<(), ParserError> {
+        let source = self.source(input);
+        let input = source.as_ref();
+        self.consumed = 0;
+        for (offset, line, line_break) in lines_with_offsets(input) {
+            self.line_offset = offset;
+            self.consumed = offset;
+            self.lex_next_line(line, line_break)
+                .map_err(|error| error.with_snippet(input))?;
+        }
+        self.finish().map_err(|error| error.with_snippet(input))?;
+        self.consumed = input.len();
+
+        Ok(())
+    }
+
+    // Reports a heredoc or doc comment still open at the end of input. Call
+    // once the last line has been lexed; pending blocks are discarded either way.
+    pub fn finish(&mut self) -> Result<(), ParserError> {
+        let unterminated = [
+            (self.heredoc.take(), "heredoc"),
+            (self.doc_block.take(), "doc comment"),
+            (self.string.take(), "string literal"),
+        ];
+        match unterminated
+            .into_iter()
+            .find_map(|(block, what)| Some((block?, what)))
+        {
+            Some((block, what)) => Err(ParserError::SyntaxError {
+                line: block.line,
+                column: block.column,
+                message: format!("unterminated {}: expected {}", what, block.terminator),
+                snippet: String::new(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // Turns a finished block into a token ending at byte `end` of the input,
+    // placed where its opening marker was.
+    fn emit_block(
+        &mut self,
+        block: PendingBlock,
+        kind: TokenType,
+        value: String,
+        raw: Option<String>,
+        end: usize,
+    ) {
+        let token = Token {
+            kind,
+            value,
+            line: block.line,
+            column: block.column,
+            raw,
+            span: Span::new(block.start, end),
+            payload: (),
+        };
+        self.tokens
+            .insert(block.index.min(self.tokens.len()), token);
+    }
+
+    // Feeds `line` to the open heredoc, if any, emitting its token when
+    // `line` is the terminator. Returns false when no heredoc is open.
+    fn heredoc_line(&mut self, line: &str) -> bool {
+        let Some(doc) = self.heredoc.as_mut() else {
+            return false;
+        };
+        if line != doc.terminator {
+            doc.body.push(line.to_string());
+            return true;
+        }
+        let doc = self.heredoc.take().unwrap();
+        let value = doc.body.join("\n");
+        let end = self.line_offset + line.len();
+        self.emit_block(doc, TokenType::StringLiteral, value, None, end);
+        true
+    }
+
+    // Feeds `line` to the open block doc comment, if any. Returns the part
+    // of `line` left to lex once the comment closes, or `None` while it is
+    // still open. What is returned is always a suffix of `line`.
+    fn doc_block_line<'l>(&mut self, line: &'l str) -> Option<&'l str> {
+        let Some(block) = self.doc_block.as_mut() else {
+            return Some(line);
+        };
+        block.source.push_str(&self.line_break);
+        let Some(end) = line.find(block.terminator.as_str()) else {
+            block.body.push(line.to_string());
+            block.source.push_str(line);
+            return None;
+        };
+        let close = end + block.terminator.len();
+        block.body.push(line[..end].to_string());
+        block.source.push_str(&line[..close]);
+        let mut block = self.doc_block.take().unwrap();
+        let raw = std::mem::take(&mut block.source);
+        let value = block.body.join("\n").trim().to_string();
+        self.emit_block(
+            block,
+            TokenType::DocComment,
+            value,
+            Some(raw),
+            self.line_offset + close,
+        );
+        self.line_offset += close;
+        Some(&line[close..])
+    }
+
+    // Feeds `line` to the open continued string, if any. Returns the part of
+    // `line` left to lex once the string closes, or `None` while it is still
+    // open. What is returned is always a suffix of `line`.
+    fn string_line<'l>(&mut self, line: &'l str) -> Result<Option<&'l str>, ParserError> {
+        let Some(string) = self.string.as_mut() else {
+            return Ok(Some(line));
+        };
+        let before = string.body.iter().map(|l| l.len() + 1).sum::<usize>();
+        string.body.push(line.to_string());
+        string.source.push_str(&self.line_break);
+        string.source.push_str(line);
+        // Decoded with `\n` between lines, which is what a backslash before
+        // the line break continues over.
+        let text = string.body.join("\n");
+        let Some((value, end)) = decode_string(&text, 0) else {
+            if continues_string(&text) {
+                return Ok(None);
+            }
+            let string = self.string.take().unwrap();
+            return Err(ParserError::SyntaxError {
+                line: string.line,
+                column: string.column,
+                message: "unterminated string literal".to_string(),
+                snippet: String::new(),
+            });
+        };
+        let string = self.string.take().unwrap();
+        let close = end - before;
+        let raw = Some(string.source[..string.source.len() - line.len() + close].to_string());
+        self.emit_block(
+            string,
+            TokenType::StringLiteral,
+            value,
+            raw,
+            self.line_offset + close,
+        );
+        self.line_offset += close;
+        Ok(Some(&line[close..]))
+    }
+
+    // Line and column to store on a token starting at `column` of the
+    // current line.
+    fn position(&self, column: usize) -> (usize, usize) {
+        if self.config.track_positions {
+            (self.line, column)
+        } else {
+            (0, 0)
+        }
+    }
+
+    // Byte offset just past `<<IDENT` when heredocs are enabled and one
+    // starts at `start`.
+    fn heredoc_marker(&self, line: &str, start: usize) -> Option<usize> {
+        if !self.config.heredocs || self.heredoc.is_some() {
+            return None;
+        }
+        let name = line[start..].strip_prefix("<<")?;
+        let len = name
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(name.len());
+        (len > 0).then_some(start + 2 + len)
+    }
+
+    // Bytes of the most recent `lex` input that were fully processed. After
+    // an error this is the offset of the token that failed.
+    pub fn bytes_consumed(&self) -> usize {
+        self.consumed
+    }
+
+    // Lexes `line` as the line following the last one seen, or as several
+    // lines when it holds Unicode separators and those are enabled.
+    // `line_break` is what ended `line` in the source, kept in the spelling
+    // of a token that continues onto the next line.
+    pub(crate) fn lex_next_line(
+        &mut self,
+        line: &str,
+        line_break: &str,
+    ) -> Result<(), ParserError> {
+        let result = if self.config.unicode_line_separators {
+            self.lex_separated_line(line)
+        } else {
+            self.lex_single_line(line)
+        };
+        self.line_break.clear();
+        self.line_break.push_str(line_break);
+        result
+    }
+
+    fn lex_separated_line(&mut self, line: &str) -> Result<(), ParserError> {
+        let mut offset = self.line_offset;
+        for segment in line.split_inclusive(['\u{2028}', '\u{2029}']) {
+            let text = segment.trim_end_matches(['\u{2028}', '\u{2029}']);
+            self.line_offset = offset;
+            self.lex_single_line(text)?;
+            self.line_break.clear();
+            self.line_break.push_str(&segment[text.len()..]);
+            offset += segment.len();
+        }
+        Ok(())
+    }
+
+    fn lex_single_line(&mut self, line: &str) -> Result<(), ParserError> {
+        self.line += 1;
+        if self.heredoc_line(line) {
+            return Ok(());
+        }
+        match self.config.delimited {
+            Some(mode) => {
+                self.lex_delimited(line, mode);
+                Ok(())
+            }
+            None => {
+                let Some(rest) = self.string_line(line)? else {
+                    return Ok(());
+                };
+                match self.doc_block_line(rest) {
+                    Some(rest) => {
+                        let col = line[..line.len() - rest.len()].chars().count() + 1;
+                        self.lex_line(rest, col).map(|_| ())
+                    }
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    // Lexes `input`, capturing everything between `open` and the next `close`
+    // verbatim as a single `kind` token (e.g. `<% ... %>` template blocks).
+    // Regions may span lines; text outside them is lexed normally. Markers are
+    // matched before anything else, including inside would-be string literals.
+    pub fn scan_raw_region(
+        &mut self,
+        input: &str,
+        open: &str,
+        close: &str,
+        kind: TokenType,
+    ) -> Result<(), ParserError> {
+        if open.is_empty() {
+            return self.lex(input);
+        }
+        let source = self.source(input);
+        let input = source.as_ref();
+
+        // Content, line, column and start offset of a region still waiting
+        // for `close`.
+        let mut region: Option<(String, usize, usize, usize)> = None;
+
+        let mut line_break = "";
+        for (offset, line, next_break) in lines_with_offsets(input) {
+            self.line += 1;
+            self.line_offset = offset;
+            self.line_break.clear();
+            self.line_break
+                .push_str(std::mem::replace(&mut line_break, next_break));
+            if region.is_none() && self.heredoc_line(line) {
+                continue;
+            }
+            let mut rest = line;
+            if region.is_none() {
+                let Some(after) = self.string_line(line)? else {
+                    continue;
+                };
+                match self.doc_block_line(after) {
+                    Some(after) => rest = after,
+                    None => continue,
+                }
+            }
+
+            loop {
+                let col = line[..line.len() - rest.len()].chars().count() + 1;
+                let rest_offset = offset + line.len() - rest.len();
+                if let Some((content, ..)) = region.as_mut() {
+                    let Some(end) = rest.find(close) else {
+                        content.push_str(rest);
+                        content.push('\n');
+                        break;
+                    };
+                    content.push_str(&rest[..end]);
+                    let (value, line, column, start) = region.take().unwrap();
+                    let span = Span::new(start, rest_offset + end + close.len());
+                    self.tokens.push(Token {
+                        kind: kind.clone(),
+                        raw: Some(input[span.start..span.end].to_string()),
+                        payload: (),
+                        value,
+                        line,
+                        column,
+                        span,
+                    });
+                    rest = &rest[end + close.len()..];
+                } else {
+                    self.line_offset = rest_offset;
+                    let Some(at) = rest.find(open) else {
+                        self.lex_line(rest, col)?;
+                        break;
+                    };
+                    let col = self.lex_line(&rest[..at], col)?;
+                    let (line, col) = self.position(col);
+                    region = Some((String::new(), line, col, rest_offset + at));
+                    rest = &rest[at + open.len()..];
+                }
+            }
+        }
+
+        match region {
+            Some((_, line, column, _)) => Err(ParserError::SyntaxError {
+                line,
+                column,
+                message: format!("unterminated region: expected {}", close),
+                snippet: String::new(),
+            }),
+            None => self.finish(),
+        }
+    }
+
+    // Scans `line` a character at a time, skipping whitespace. At each
+    // token start it tries, in order: heredoc and block doc comment openers,
+    // unrecognized noise, raw identifiers, doc and line comment markers
+    // (which take the rest of the line), sigils, the longest registered
+    // operator, a string opened by any of `string_quotes`, multi-word
+    // keywords, and finally a word cut by `word_end`. A block doc comment, or a string continued by a
+    // trailing backslash, that is still open at the end of the line carries
+    // over to the next one. Columns count characters from one, with `col`
+    // being the column `line` starts at; returns the column just past the
+    // end of `line`.
+    pub(crate) fn lex_line(&mut self, line: &str, mut col: usize) -> Result<usize, ParserError> {
+        let comment = self.comment_marker();
+        let mut pos = 0;
+        self.scans.bytes_examined += line.len();
+
+        while let Some(c) = line[pos..].chars().next() {
+            if c.is_whitespace() {
+                pos += c.len_utf8();
+                col += 1;
+                continue;
+            }
+
+            let start = pos;
+            self.consumed = self.line_offset + start;
+            if let Some(end) = self.heredoc_marker(line, start) {
+                let (line_no, column) = self.position(col);
+                self.heredoc = Some(PendingBlock {
+                    terminator: line[start + 2..end].to_string(),
+                    body: Vec::new(),
+                    source: String::new(),
+                    line: line_no,
+                    column,
+                    start: self.line_offset + start,
+                    index: self.tokens.len(),
+                });
+                col += line[start..end].chars().count();
+                pos = end;
+                continue;
+            }
+            if let Some((open, close)) = self.doc_block_markers() {
+                if line[start..].starts_with(open) {
+                    let body = start + open.len();
+                    let Some(end) = line[body..].find(close) else {
+                        let (line_no, column) = self.position(col);
+                        self.doc_block = Some(PendingBlock {
+                            terminator: close.to_string(),
+                            body: vec![line[body..].to_string()],
+                            source: line[start..].to_string(),
+                            line: line_no,
+                            column,
+                            start: self.line_offset + start,
+                            index: self.tokens.len(),
+                        });
+                        break;
+                    };
+                    pos = body + end + close.len();
+                    let (line_no, column) = self.position(col);
+                    self.tokens.push(Token {
+                        kind: TokenType::DocComment,
+                        value: line[body..body + end].trim().to_string(),
+                        line: line_no,
+                        column,
+                        raw: Some(line[start..pos].to_string()),
+                        span: Span::new(self.line_offset + start, self.line_offset + pos),
+                        payload: (),
+                    });
+                    if self.config.track_positions {
+                        col += line[start..pos].chars().count();
+                    }
+                    continue;
+                }
+            }
+            if self.is_unrecognized(c) {
+                pos = line[start..]
+                    .find(|c: char| c.is_whitespace() || !self.is_unrecognized(c))
+                    .map_or(line.len(), |len| start + len);
+                if self.config.unknown_tokens {
+                    let (line_no, column) = self.position(col);
+                    self.tokens.push(Token {
+                        kind: TokenType::Unknown,
+                        value: line[start..pos].to_string(),
+                        line: line_no,
+                        column,
+                        raw: None,
+                        span: Span::new(self.line_offset + start, self.line_offset + pos),
+                        payload: (),
+                    });
+                }
+                if self.config.track_positions {
+                    col += line[start..pos].chars().count();
+                }
+                continue;
+            }
+            let (kind, value, raw) = if let Some(name) = self.raw_identifier(line, start) {
+                self.scans.identifier_scans += 1;
+                pos = name.end;
+                self.check_identifier_length(&line[name.clone()], col)?;
+                (
+                    TokenType::Identifier,
+                    line[name].to_string(),
+                    Some(line[start..pos].to_string()),
+                )
+            } else if let Some(marker) = self
+                .doc_comment_marker()
+                .filter(|m| line[start..].starts_with(m))
+            {
+                pos = line.len();
+                let text = line[start + marker.len()..].trim().to_string();
+                (TokenType::DocComment, text, Some(line[start..].to_string()))
+            } else if let Some(marker) = comment.filter(|m| line[start..].starts_with(m)) {
+                if !self.config.keep_comments {
+                    if self.config.track_positions {
+                        col += line[start..].chars().count();
+                    }
+                    break;
+                }
+                pos = line.len();
+                let text = line[start + marker.len()..].trim().to_string();
+                (TokenType::Comment, text, Some(line[start..].to_string()))
+            } else if self.config.sigils.contains(&c) {
+                pos = self.sigil_end(line, start, c);
+                (TokenType::Sigil, line[start..pos].to_string(), None)
+            } else if let Some(len) = self.match_operator(&line[start..]) {
+                self.scans.operator_scans += 1;
+                pos = start + len;
+                (TokenType::Operator, line[start..pos].to_string(), None)
+            } else if self.config.string_quotes.contains(&c) {
+                self.scans.string_scans += 1;
+                let Some((value, end)) = self.scan_string(line, start, col)? else {
+                    let (line_no, column) = self.position(col);
+                    self.string = Some(PendingBlock {
+                        terminator: c.to_string(),
+                        body: vec![line[start..].to_string()],
+                        source: line[start..].to_string(),
+                        line: line_no,
+                        column,
+                        start: self.line_offset + start,
+                        index: self.tokens.len(),
+                    });
+                    break;
+                };
+                pos = end;
+                (
+                    TokenType::StringLiteral,
+                    value,
+                    Some(line[start..end].to_string()),
+                )
+            } else if let Some((end, phrase)) = self.keyword_phrase(line, start) {
+                self.scans.identifier_scans += 1;
+                pos = end;
+                let source = &line[start..end];
+                let raw = (source != phrase).then(|| source.to_string());
+                (TokenType::Keyword, phrase.to_string(), raw)
+            } else {
+                pos = self.word_end(line, start);
+                if self.splits_punctuation(&line[start..pos]) {
+                    // Emit the first character only; the rest of the run is
+                    // picked up again on the next iteration.
+                    pos = start + c.len_utf8();
+                }
+                let word = &line[start..pos];
+                let kind = self.classify(word);
+                match kind {
+                    TokenType::Literal | TokenType::Float => self.scans.number_scans += 1,
+                    TokenType::Identifier | TokenType::Keyword | TokenType::Boolean => {
+                        self.scans.identifier_scans += 1
+                    }
+                    _ => {}
+                }
+                if kind == TokenType::Punctuation {
+                    self.check_malformed_number(word, col)?;
+                }
+                if kind == TokenType::Literal && self.config.check_integer_bounds {
+                    self.check_integer_bounds(word, col)?;
+                }
+                if kind == TokenType::Identifier {
+                    self.check_identifier_length(word, col)?;
+                }
+                (kind, word.to_string(), None)
+            };
+
+            let (kind, value, raw) = match self.config.operator_aliases.get(&value) {
+                Some(canonical) if raw.is_none() => {
+                    (TokenType::Operator, canonical.clone(), Some(value))
+                }
+                _ => (kind, value, raw),
+            };
+
+            let (line_no, column) = self.position(col);
+            let token = Token {
+                kind,
+                value,
+                line: line_no,
+                column,
+                raw,
+                span: Span::new(self.line_offset + start, self.line_offset + pos),
+                payload: (),
+            };
+            self.tokens.push(token);
+            if self.config.track_positions {
+                col += line[start..pos].chars().count();
+            }
+        }
+
+        Ok(col)
+    }
+
+    // A sigil is a single character unless attachment is enabled and an
+    // identifier follows immediately, in which case the two form one token.
+    // The identifier runs as in `is_identifier`: a letter or `_`, then
+    // letters, digits and `_`.
+    fn sigil_end(&self, line: &str, start: usize, sigil: char) -> usize {
+        let name_start = start + sigil.len_utf8();
+        let starts_name = |c: char| c.is_alphabetic() || c == '_';
+        if !self.config.attach_sigils || !line[name_start..].starts_with(starts_name) {
+            return name_start;
+        }
+        line[name_start..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(line.len(), |len| name_start + len)
+    }
+
+    // End of the word starting at `start`. Words are cut wherever the kind
+    // of character changes, so `if(x)` or `x+5` need no spaces: a word is
+    // either a run of letters, digits and `_` (a number may carry a decimal
+    // part, and a registered radix prefix stays attached to its digits), or
+    // a run of anything else up to the next space, quote, comment, operator,
+    // sigil or word character.
+    fn word_end(&self, line: &str, start: usize) -> usize {
+        let rest = &line[start..];
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let is_digit = |c: char| c.is_ascii_digit();
+        let run = |from: usize, pred: &dyn Fn(char) -> bool| {
+            rest[from..]
+                .find(|c: char| !pred(c))
+                .map_or(rest.len(), |len| from + len)
+        };
+        let first = rest.chars().next().unwrap_or(' ');
+        let radix_prefix = self
+            .config
+            .radix_prefixes
+            .keys()
+            .filter(|p| !p.is_empty() && rest.starts_with(p.as_str()))
+            .filter(|p| rest[p.len()..].starts_with(is_word))
+            .map(String::len)
+            .max();
+
+        let end = if let Some(len) = radix_prefix {
+            run(len, &is_word)
+        } else if is_digit(first) {
+            // Further `.digits` groups stay attached, so `3.14.15` reaches
+            // the lexer whole and is rejected instead of becoming two floats.
+            let mut end = run(0, &is_word);
+            let mut dotted = false;
+            while let Some(frac) = rest[end..].strip_prefix('.') {
+                if frac.starts_with(is_digit) {
+                    end = run(end + 1, &is_word);
+                } else if self.config.allow_edge_dot_floats && !dotted {
+                    end += 1;
+                } else {
+                    break;
+                }
+                dotted = true;
+            }
+            end
+        } else if first == '.'
+            && self.config.allow_edge_dot_floats
+            && rest[1..].starts_with(is_digit)
+        {
+            run(1, &is_digit)
+        } else if is_word(first) {
+            run(0, &is_word)
+        } else {
+            rest.char_indices()
+                .skip(1)
+                .find(|&(i, c)| {
+                    c.is_whitespace()
+                        || is_word(c)
+                        || self.config.string_quotes.contains(&c)
+                        || self.config.sigils.contains(&c)
+                        || self.is_unrecognized(c)
+                        || self.starts_comment(&rest[i..])
+                        || self.match_operator(&rest[i..]).is_some()
+                })
+                .map_or(rest.len(), |(i, _)| i)
+        };
+        start + end
+    }
+
+    // Characters no token rule is meant for. Only reported when
+    // `skip_unrecognized` is set; otherwise they lex as punctuation.
+    fn is_unrecognized(&self, c: char) -> bool {
+        self.config.skip_unrecognized && (c.is_control() || (!c.is_ascii() && !c.is_alphanumeric()))
+    }
+
+    // End offset and spelling of the longest keyword phrase starting at
+    // `start`, if any.
+    fn keyword_phrase(&self, line: &str, start: usize) -> Option<(usize, &'a str)> {
+        if self.config.keyword_phrases.is_empty() {
+            return None;
+        }
+        let first_end = self.word_end(line, start);
+        let first = &line[start..first_end];
+        let mut best: Option<(usize, &'a str)> = None;
+        for phrase in &self.config.keyword_phrases {
+            let mut words = phrase.split_whitespace();
+            if words.next() != Some(first) {
+                continue;
+            }
+            let mut end = first_end;
+            let matched = words.all(|word| {
+                let at = line.len() - line[end..].trim_start().len();
+                let next_end = self.word_end(line, at);
+                let found = at > end && &line[at..next_end] == word;
+                end = next_end;
+                found
+            });
+            if matched && end > first_end && best.is_none_or(|(best_end, _)| end > best_end) {
+                best = Some((end, phrase.as_str()));
+            }
+        }
+        best
+    }
+
+    fn splits_punctuation(&self, word: &str) -> bool {
+        !self.config.group_punctuation
+            && word.chars().nth(1).is_some()
+            && word.chars().all(|c| !c.is_alphanumeric())
+    }
+
+    fn comment_marker(&self) -> Option<&'a str> {
+        self.config
+            .line_comment
+            .as_deref()
+            .filter(|m| !m.is_empty())
+    }
+
+    // Byte range of the word after a raw identifier prefix at `start`. The
+    // prefix alone, with no word after it, is not a raw identifier.
+    fn raw_identifier(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        let prefix = self
+            .config
+            .raw_identifier_prefix
+            .as_deref()
+            .filter(|p| !p.is_empty())?;
+        let name = start + prefix.len();
+        if !line[start..].starts_with(prefix) {
+            return None;
+        }
+        let len = line[name..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(line.len() - name);
+        (len > 0).then_some(name..name + len)
+    }
+
+    fn doc_comment_marker(&self) -> Option<&'a str> {
+        self.config.doc_comment.as_deref().filter(|m| !m.is_empty())
+    }
+
+    fn doc_block_markers(&self) -> Option<(&'a str, &'a str)> {
+        self.config
+            .doc_block_comment
+            .as_ref()
+            .map(|(open, close)| (open.as_str(), close.as_str()))
+            .filter(|(open, close)| !open.is_empty() && !close.is_empty())
+    }
+
+    // Whether any kind of comment starts at the beginning of `text`.
+    fn starts_comment(&self, text: &str) -> bool {
+        [
+            self.comment_marker(),
+            self.doc_comment_marker(),
+            self.doc_block_markers().map(|m| m.0),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|marker| text.starts_with(marker))
+    }
+
+    // String tokens take the decoded content, without the surrounding
+    // quotes, as their `value`; the quoted source spelling goes in `raw`.
+    // A missing closing quote is reported at the opening one, unless
+    // `string_continuation` is on and the line ends in a backslash, in which
+    // case the string carries on and `None` is returned.
+    fn scan_string(
+        &self,
+        line: &str,
+        start: usize,
+        column: usize,
+    ) -> Result<Option<(String, usize)>, ParserError> {
+        if let Some(string) = decode_string(line, start) {
+            return Ok(Some(string));
+        }
+        if self.config.string_continuation && continues_string(&line[start..]) {
+            return Ok(None);
+        }
+        Err(ParserError::SyntaxError {
+            line: self.line,
+            column,
+            message: "unterminated string literal".to_string(),
+            snippet: String::new(),
+        })
+    }
+
+    // Emits one `Literal` per field with escapes removed from its value.
+    fn lex_delimited(&mut self, line: &str, mode: DelimitedMode) {
+        if line.is_empty() {
+            return;
+        }
+
+        // Decoded value, starting column and byte range of each field.
+        let mut fields = vec![(String::new(), 1, 0, line.len())];
+        let mut chars = line.char_indices();
+        let mut col = 0;
+        while let Some((i, c)) = chars.next() {
+            col += 1;
+            let field = fields.last_mut().unwrap();
+            if c == mode.escape {
+                if let Some((_, escaped)) = chars.next() {
+                    field.0.push(escaped);
+                    col += 1;
+                }
+            } else if c == mode.separator {
+                field.3 = i;
+                fields.push((String::new(), col + 1, i + c.len_utf8(), line.len()));
+            } else {
+                field.0.push(c);
+            }
+        }
+
+        for (value, column, start, end) in fields {
+            let source = &line[start..end];
+            let (line, column) = self.position(column);
+            self.tokens.push(Token {
+                kind: TokenType::Literal,
+                raw: (source != value).then(|| source.to_string()),
+                value,
+                line,
+                column,
+                span: Span::new(self.line_offset + start, self.line_offset + end),
+                payload: (),
+            });
+        }
+    }
+
+    // Digits with several decimal points, like `3.14.15`, or a word behind
+    // a numeric radix prefix that is not a valid literal, like `0xG1` or a
+    // bare `0x`. Radix errors point at the first bad digit, or just past the
+    // prefix when there are none. Prefixes such as `$` that do not start
+    // with a digit are left alone, since the word may be something else.
+    fn check_malformed_number(&self, word: &str, column: usize) -> Result<(), ParserError> {
+        let error = |column: usize, message: String| {
+            Err(ParserError::SyntaxError {
+                line: self.line,
+                column,
+                message,
+                snippet: String::new(),
+            })
+        };
+        if word.matches('.').count() > 1 && word.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+            return error(column, format!("malformed number literal: {}", word));
+        }
+        let prefix = self
+            .config
+            .radix_prefixes
+            .iter()
+            .filter(|(p, base)| (2..=36).contains(*base) && word.starts_with(p.as_str()))
+            .filter(|(p, _)| p.starts_with(|c: char| c.is_ascii_digit()))
+            .max_by_key(|(p, _)| p.len());
+        if let Some((prefix, &base)) = prefix {
+            let digits = &word[prefix.len()..];
+            let bad = digits.chars().position(|c| !c.is_digit(base)).unwrap_or(0);
+            let column = column + prefix.chars().count() + bad;
+            return error(column, format!("invalid base-{} literal: {}", base, word));
+        }
+        Ok(())
+    }
+
+    fn check_identifier_length(&self, word: &str, column: usize) -> Result<(), ParserError> {
+        match self.config.max_identifier_length {
+            Some(max) if word.chars().count() > max => Err(ParserError::SyntaxError {
+                line: self.line,
+                column,
+                message: format!("identifier too long: {} exceeds {} characters", word, max),
+                snippet: String::new(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_integer_bounds(&self, word: &str, column: usize) -> Result<(), ParserError> {
+        let fits = match self.config.radix_literal(word) {
+            Some((base, digits)) => u128::from_str_radix(digits, base).is_ok(),
+            None => !word.bytes().all(|b| b.is_ascii_digit()) || word.parse::<u128>().is_ok(),
+        };
+        if !fits {
+            return Err(ParserError::SyntaxError {
+                line: self.line,
+                column,
+                message: format!("integer literal too large: {}", word),
+                snippet: String::new(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    // Hands back the tokens and resets everything tied to the current
+    // input: the line cursor, byte offsets, and any heredoc, string or doc
+    // comment left open. The tokens are handed over without copying; the
+    // classification cache and `scans` counts carry over.
+    pub fn drain_tokens(&mut self) -> Vec<Token> {
+        self.line = 0;
+        self.line_offset = 0;
+        self.consumed = 0;
+        self.heredoc = None;
+        self.string = None;
+        self.doc_block = None;
+        self.line_break.clear();
+        std::mem::take(&mut self.tokens)
+    }
+}
@@ -1,179 +1,5089 @@
 // Minimal Parsing PoC Project
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fmt;
+use std::hash::Hash;
+use std::io::BufRead;
+use std::ops::{BitOr, BitOrAssign, Range};
+use std::path::Path;
+use std::time::Duration;
+
+mod lexer;
+
+use lexer::{line_at, lines_with_offsets, GapFiller};
+pub use lexer::{normalize_newlines, Lexer};
 
 // Custom Error Handling
 #[derive(Debug)]
-enum ParserError {
-    SyntaxError { 
-        line: usize, 
-        column: usize, 
-        message: String 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParserError {
+    SyntaxError {
+        line: usize,
+        column: usize,
+        message: String,
+        // Full text of the offending line, rendered under the message with
+        // a caret at `column`. Empty when the source was not at hand.
+        #[cfg_attr(feature = "serde", serde(default))]
+        snippet: String,
     },
     UnexpectedToken(String),
+    // Failure reading the input itself, kept as the error's `source`.
+    // Not serializable; serializing one fails.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Io(std::io::Error),
 }
 
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ParserError::SyntaxError { line, column, message } => 
-                write!(f, "Syntax Error at line {}, column {}: {}", line, column, message),
-            ParserError::UnexpectedToken(token) => 
-                write!(f, "Unexpected token: {}", token),
+            ParserError::SyntaxError {
+                line,
+                column,
+                message,
+                snippet,
+            } => {
+                write!(
+                    f,
+                    "Syntax Error at line {}, column {}: {}",
+                    line, column, message
+                )?;
+                if !snippet.is_empty() {
+                    let (text, caret) = render_snippet(snippet, *column);
+                    write!(f, "\n{}\n{}^", text, caret)?;
+                }
+                Ok(())
+            }
+            ParserError::UnexpectedToken(token) => write!(f, "Unexpected token: {}", token),
+            ParserError::Io(error) => write!(f, "I/O error while reading input: {}", error),
+        }
+    }
+}
+
+impl Error for ParserError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParserError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ParserError {
+    fn from(error: std::io::Error) -> Self {
+        ParserError::Io(error)
+    }
+}
+
+impl ParserError {
+    // Fills in an empty `snippet` with the error's line from `input`, the
+    // text that was being parsed. Other errors are returned unchanged.
+    pub fn with_snippet(self, input: &str) -> Self {
+        match self {
+            ParserError::SyntaxError {
+                line,
+                column,
+                message,
+                snippet,
+            } if snippet.is_empty() => {
+                let snippet = SourceBuffer::new(input)
+                    .line(line)
+                    .unwrap_or("")
+                    .to_string();
+                ParserError::SyntaxError {
+                    line,
+                    column,
+                    message,
+                    snippet,
+                }
+            }
+            other => other,
         }
     }
 }
 
-impl Error for ParserError {}
+// Widest snippet shown in full; longer lines are cut to this many
+// characters around the error column, with `...` marking each cut.
+const SNIPPET_WIDTH: usize = 80;
+
+// The part of `snippet` to display and the padding that puts a caret under
+// `column`. Padding copies tabs from the line so the caret lines up however
+// the terminal expands them.
+fn render_snippet(snippet: &str, column: usize) -> (String, String) {
+    let chars: Vec<char> = snippet.chars().collect();
+    let at = column.saturating_sub(1).min(chars.len());
+    let (start, end) = if chars.len() <= SNIPPET_WIDTH {
+        (0, chars.len())
+    } else {
+        let start = at
+            .saturating_sub(SNIPPET_WIDTH / 2)
+            .min(chars.len() - SNIPPET_WIDTH);
+        (start, start + SNIPPET_WIDTH)
+    };
+
+    let mut text = String::new();
+    let mut caret = String::new();
+    if start > 0 {
+        text.push_str("...");
+        caret.push_str("   ");
+    }
+    text.extend(&chars[start..end]);
+    if end < chars.len() {
+        text.push_str("...");
+    }
+    caret.extend(
+        chars[start..at]
+            .iter()
+            .map(|&c| if c == '\t' { '\t' } else { ' ' }),
+    );
+    (text, caret)
+}
+
+// Parse failures become `InvalidData` errors carrying the display message;
+// a wrapped I/O error is handed back unchanged.
+impl From<ParserError> for std::io::Error {
+    fn from(error: ParserError) -> Self {
+        match error {
+            ParserError::Io(error) => error,
+            other => std::io::Error::new(std::io::ErrorKind::InvalidData, other.to_string()),
+        }
+    }
+}
 
 // Trait for Parsing Strategy
-trait Parser {
+pub trait Parser {
     fn parse(&self, input: &str) -> Result<Vec<Token>, ParserError>;
     fn recover_from_error(&self, error: &ParserError) -> Option<RecoveryAction>;
+
+    fn supported_features(&self) -> FeatureSet {
+        FeatureSet::empty()
+    }
+
+    // Parses bytes that may not be valid UTF-8 by replacing each invalid
+    // sequence with U+FFFD first. Reported positions refer to that repaired
+    // string, not to offsets in the original bytes.
+    fn parse_utf8_lossy(&self, bytes: &[u8]) -> Result<Vec<Token>, ParserError> {
+        self.parse(&String::from_utf8_lossy(bytes))
+    }
+
+    // The first `n` tokens of `input`. Implementations should stop scanning
+    // once they have enough; the default parses everything and truncates.
+    fn parse_first_n(&self, input: &str, n: usize) -> Result<Vec<Token>, ParserError> {
+        let mut tokens = self.parse(input)?;
+        tokens.truncate(n);
+        Ok(tokens)
+    }
+
+    // Reads the file at `path` and parses its contents. A failed read is an
+    // `Io` error of the same kind whose message starts with the path.
+    fn parse_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Token>, ParserError>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+        let input = std::fs::read_to_string(path).map_err(|error| {
+            std::io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+        })?;
+        self.parse(&input)
+    }
+}
+
+// Parser Capability Flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FeatureSet(u8);
+
+impl FeatureSet {
+    pub const COMMENTS: FeatureSet = FeatureSet(1 << 0);
+    pub const STRINGS: FeatureSet = FeatureSet(1 << 1);
+    pub const FLOATS: FeatureSet = FeatureSet(1 << 2);
+    pub const SPANS: FeatureSet = FeatureSet(1 << 3);
+    pub const RECOVERY: FeatureSet = FeatureSet(1 << 4);
+
+    pub const fn empty() -> Self {
+        FeatureSet(0)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn contains(self, other: FeatureSet) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for FeatureSet {
+    type Output = FeatureSet;
+
+    fn bitor(self, rhs: FeatureSet) -> FeatureSet {
+        FeatureSet(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for FeatureSet {
+    fn bitor_assign(&mut self, rhs: FeatureSet) {
+        self.0 |= rhs.0;
+    }
 }
 
 // Token Representation
-#[derive(Debug, Clone)]
-struct Token {
-    kind: TokenType,
-    value: String,
-    line: usize,
-    column: usize,
+//
+// `payload` carries caller data (AST ids, resolved types, ...) and defaults
+// to `()`, so plain lexer output is simply `Token`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token<T = ()> {
+    pub kind: TokenType,
+    pub value: String,
+    pub line: usize,
+    // Character position of the token's first character within its line,
+    // counting from 1. Tabs count as one character.
+    pub column: usize,
+    // Original source spelling, set only when it differs from `value`
+    // (e.g. string literals, whose `value` is the decoded content).
+    pub raw: Option<String>,
+    pub span: Span,
+    pub payload: T,
+}
+
+// Byte range of a token within the text passed to the lexer. For lexed
+// tokens `&input[span.start..span.end]` is exactly `source_text()`, with one
+// exception: a heredoc's span also covers the rest of its marker line.
+// Tokens built by hand, such as recovery replacements, carry whatever span
+// they were given. Offsets count bytes, whereas `Token::column` counts
+// characters, so the two differ on lines with multi-byte text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl<T> Token<T> {
+    pub fn map_payload<U, F: FnOnce(T) -> U>(self, f: F) -> Token<U> {
+        Token {
+            kind: self.kind,
+            value: self.value,
+            line: self.line,
+            column: self.column,
+            raw: self.raw,
+            span: self.span,
+            payload: f(self.payload),
+        }
+    }
+
+    // The token as spelled in the source: `raw` when set, else `value`.
+    pub fn source_text(&self) -> &str {
+        self.raw.as_deref().unwrap_or(&self.value)
+    }
+
+    // One-line form for snapshot tests, `Identifier("foo")@1:3`: kind, value
+    // with quotes, backslashes and control characters escaped, then line and
+    // column. Unlike `Debug` it ignores `raw`, `span` and `payload`.
+    pub fn to_snapshot_string(&self) -> String {
+        format!(
+            "{}(\"{}\")@{}:{}",
+            self.kind,
+            self.value.escape_debug(),
+            self.line,
+            self.column
+        )
+    }
+
+    // Replaces the token's text, e.g. to rename an identifier. `value`
+    // becomes the new spelling (`raw` is cleared) and the span is resized to
+    // `start + new_value.len()`. Tokens after this one keep their old spans
+    // until reflowed.
+    pub fn replace_value(&mut self, new_value: String) {
+        self.span.end = self.span.start + new_value.len();
+        self.value = new_value;
+        self.raw = None;
+    }
+
+    pub fn is_at(&self, line: usize, column: usize) -> bool {
+        self.line == line && self.column == column
+    }
+
+    // TextMate-style scope name for editor theming.
+    pub fn semantic_category(&self) -> &'static str {
+        match self.kind {
+            TokenType::Identifier => "variable",
+            TokenType::Literal => "constant.numeric",
+            TokenType::Operator => "keyword.operator",
+            TokenType::Keyword => "keyword.control",
+            TokenType::Punctuation => "punctuation",
+            TokenType::Boolean => "constant.language",
+            TokenType::StringLiteral => "string.quoted",
+            TokenType::Comment => "comment.line",
+            TokenType::Sigil => "punctuation.definition",
+            TokenType::Float => "constant.numeric",
+            TokenType::DocComment => "comment.block.documentation",
+            TokenType::Unknown => "invalid",
+            TokenType::Eof => "meta.eof",
+            TokenType::Whitespace => "meta.whitespace",
+        }
+    }
+}
+
+impl<T: Clone> Token<T> {
+    // Fuses `other` onto the end of `self` when both have the same kind and
+    // `other` starts exactly where `self` ends.
+    pub fn merge_with(&self, other: &Token<T>) -> Option<Token<T>> {
+        if self.kind != other.kind || self.span.end != other.span.start {
+            return None;
+        }
+        let raw = match (&self.raw, &other.raw) {
+            (None, None) => None,
+            (left, right) => Some(format!(
+                "{}{}",
+                left.as_deref().unwrap_or(&self.value),
+                right.as_deref().unwrap_or(&other.value)
+            )),
+        };
+        Some(Token {
+            kind: self.kind.clone(),
+            value: format!("{}{}", self.value, other.value),
+            line: self.line,
+            column: self.column,
+            raw,
+            span: Span::new(self.span.start, other.span.end),
+            payload: self.payload.clone(),
+        })
+    }
+
+    // A copy whose span is relative to byte `base_offset` of the original
+    // input, e.g. the start of a `parse_range` range. Line and column are
+    // left as they were. Panics if the token starts before `base_offset`.
+    pub fn relative_to(&self, base_offset: usize) -> Token<T> {
+        let shift = |offset: usize| {
+            offset
+                .checked_sub(base_offset)
+                .expect("token starts before the base offset")
+        };
+        Token {
+            span: Span::new(shift(self.span.start), shift(self.span.end)),
+            ..self.clone()
+        }
+    }
+
+    // Like `relative_to`, also taking line and column from `slice`, the
+    // buffer of the text starting at `base_offset`.
+    pub fn relative_to_buffer(&self, base_offset: usize, slice: &SourceBuffer) -> Token<T> {
+        let mut token = self.relative_to(base_offset);
+        if let Some((line, column)) = slice.offset_to_position(token.span.start) {
+            token.line = line;
+            token.column = column;
+        }
+        token
+    }
 }
 
 // Token Type Enumeration
-#[derive(Debug, Clone, PartialEq)]
-enum TokenType {
+//
+// Discriminants count up from 0 in declaration order and end at
+// `TokenType::COUNT - 1`; a variant added at the end must become the one
+// checked against `COUNT` below.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u16)]
+pub enum TokenType {
     Identifier,
     Literal,
     Operator,
     Keyword,
     Punctuation,
+    Boolean,
+    StringLiteral,
+    Comment,
+    Sigil,
+    // Decimal number; only produced with `ParserConfig::float_tokens`.
+    Float,
+    DocComment,
+    Unknown,
+    // Empty marker closing the stream; only produced with `ParserConfig::emit_eof`.
+    Eof,
+    // Spaces, tabs or line breaks; only produced with `ParserConfig::lossless`.
+    Whitespace,
+}
+
+// Fails to build when `COUNT` no longer sits just past the last variant.
+const _: () = assert!(TokenType::Whitespace as u16 + 1 == TokenType::COUNT);
+
+impl TokenType {
+    // Number of variants.
+    pub const COUNT: u16 = 14;
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenType::Identifier => "Identifier",
+            TokenType::Literal => "Literal",
+            TokenType::Operator => "Operator",
+            TokenType::Keyword => "Keyword",
+            TokenType::Punctuation => "Punctuation",
+            TokenType::Boolean => "Boolean",
+            TokenType::StringLiteral => "StringLiteral",
+            TokenType::Comment => "Comment",
+            TokenType::Sigil => "Sigil",
+            TokenType::Float => "Float",
+            TokenType::DocComment => "DocComment",
+            TokenType::Unknown => "Unknown",
+            TokenType::Eof => "Eof",
+            TokenType::Whitespace => "Whitespace",
+        }
+    }
+
+    // Whitespace and comments: tokens that carry no meaning for the code.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Whitespace | TokenType::Comment | TokenType::DocComment
+        )
+    }
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 // Recovery Mechanism
 #[derive(Debug)]
-enum RecoveryAction {
+pub enum RecoveryAction {
     Skip,
     Replace(Token),
     Synchronize(usize),
 }
 
-// Minimal Parsing Implementation
-struct MinimalParser {
-    // Configuration and state can be added here
+impl fmt::Display for RecoveryAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecoveryAction::Skip => write!(f, "skip"),
+            RecoveryAction::Replace(token) => write!(f, "replace with {}", token.value),
+            RecoveryAction::Synchronize(line) => write!(f, "synchronize to line {}", line),
+        }
+    }
 }
 
-impl Parser for MinimalParser {
-    fn parse(&self, input: &str) -> Result<Vec<Token>, ParserError> {
-        let mut tokens = Vec::new();
-        let mut lines = input.lines().enumerate();
-        
-        // Placeholder parsing logic
-        for (line_num, line) in lines {
-            // Basic token extraction (oversimplified)
-            for (col, word) in line.split_whitespace().enumerate() {
-                let token = Token {
-                    kind: self.classify_token(word),
-                    value: word.to_string(),
-                    line: line_num + 1,
-                    column: col,
-                };
-                tokens.push(token);
+// Errors met by `MinimalParser::parse_recovering`, each with the action that
+// was taken to get past it, in the order they occurred.
+#[derive(Debug, Default)]
+pub struct RecoveryLog {
+    entries: Vec<(ParserError, RecoveryAction)>,
+}
+
+impl RecoveryLog {
+    pub fn entries(&self) -> &[(ParserError, RecoveryAction)] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// Parser Configuration
+#[derive(Debug, Clone)]
+pub struct ParserConfig {
+    // Words classified as `TokenType::Keyword`.
+    pub keywords: HashSet<String>,
+    // Exact spellings classified as `TokenType::Operator`.
+    pub operators: HashSet<String>,
+    // Classify `true`/`false` as `TokenType::Boolean` instead of identifiers.
+    pub recognize_booleans: bool,
+    // Reject integer literals that do not fit in a `u128`.
+    pub check_integer_bounds: bool,
+    // Split lines into separator-delimited fields instead of words.
+    pub delimited: Option<DelimitedMode>,
+    // Reject identifiers longer than this many characters.
+    pub max_identifier_length: Option<usize>,
+    // Memoize `classify` results for up to this many distinct words.
+    pub classification_cache_size: Option<usize>,
+    // Marker that starts a comment running to the end of the line.
+    pub line_comment: Option<String>,
+    // Emit `line_comment` comments as `TokenType::Comment` tokens. When
+    // off they are dropped along with the rest of their line. On by default.
+    pub keep_comments: bool,
+    // Characters lexed as standalone `TokenType::Sigil` tokens.
+    pub sigils: HashSet<char>,
+    // Fold a sigil and the identifier right after it into one token (`@name`).
+    pub attach_sigils: bool,
+    // Number prefixes mapped to their base, e.g. `"$"` -> 16 for `$FF`.
+    // Bases outside 2..=36 are ignored. Defaults to `0x`/`0X` for hex and
+    // `0b`/`0B` for binary.
+    pub radix_prefixes: HashMap<String, u32>,
+    // Accept floats with nothing before or after the dot (`.5`, `42.`).
+    // On by default.
+    pub allow_edge_dot_floats: bool,
+    // Classify decimal numbers as `TokenType::Float`. Off by default, where
+    // they are `Literal` like integers; `is_float_literal` tells them apart.
+    pub float_tokens: bool,
+    // Characters that open a string literal; each is closed by itself.
+    pub string_quotes: HashSet<char>,
+    // Keep runs like `);` as one token instead of one token per character.
+    pub group_punctuation: bool,
+    // Treat `<<IDENT` as the start of a heredoc whose body runs until a line
+    // consisting of exactly `IDENT`.
+    pub heredocs: bool,
+    // Let a string literal whose line ends in a backslash continue on the
+    // next line. The backslash and line break are left out of `value`.
+    pub string_continuation: bool,
+    // Record line and column on each token. When off, tokens report `0, 0`
+    // and the lexer skips counting characters; errors keep their line.
+    pub track_positions: bool,
+    // Drop underscores from a word before looking it up in `keywords`, so
+    // `go_to` matches the keyword `goto`.
+    pub underscore_insensitive_keywords: bool,
+    // Marker for doc comments running to the end of the line, e.g. `///`.
+    // Checked before `line_comment`, so it may extend that marker.
+    pub doc_comment: Option<String>,
+    // Open and close markers of block doc comments, e.g. `/**` and `*/`.
+    // These may span lines.
+    pub doc_block_comment: Option<(String, String)>,
+    // Drop runs of control characters and non-ASCII symbols (binary noise,
+    // U+FFFD) instead of lexing them as punctuation.
+    pub skip_unrecognized: bool,
+    // With `skip_unrecognized`, emit one `TokenType::Unknown` token for each
+    // dropped run rather than nothing.
+    pub unknown_tokens: bool,
+    // Also end lines at U+2028 LINE SEPARATOR and U+2029 PARAGRAPH
+    // SEPARATOR, as JavaScript does. Off by default, where they count as
+    // whitespace within a line.
+    pub unicode_line_separators: bool,
+    // Rewrite `\r\n` and lone `\r` line endings as `\n` before lexing.
+    // Lines are counted the same either way; spans then index the
+    // rewritten text instead of the input.
+    pub normalize_newlines: bool,
+    // Prefix that turns the word right after it into an identifier even if
+    // it is a keyword, e.g. `r#` for `r#type`. The token's `value` is the
+    // bare word and `raw` keeps the prefix.
+    pub raw_identifier_prefix: Option<String>,
+    // Multi-word keywords such as `"else if"`, lexed as one `Keyword` token
+    // when their words follow each other on a line separated only by
+    // whitespace. `value` is the phrase as listed here; `raw` keeps the
+    // source spelling when the spacing differs. The longest match wins.
+    pub keyword_phrases: Vec<String>,
+    // End `parse` output with an empty `TokenType::Eof` token positioned
+    // just past the last character. After a trailing newline that is the
+    // start of the following, empty line.
+    pub emit_eof: bool,
+    // Keep everything: runs of spaces and tabs and runs of line breaks
+    // become `TokenType::Whitespace` tokens, text the config would drop
+    // (skipped comments, unrecognized characters) becomes `Unknown`, and
+    // every `value` is the exact source spelling, so concatenating the
+    // values of `parse` output gives back the input. Heredocs are the
+    // exception, as their spans overlap the rest of their marker line.
+    pub lossless: bool,
+    // Alternative spellings of operators mapped to their canonical form,
+    // e.g. `"and"` -> `"&&"`. A word or operator token spelled as a key
+    // becomes an `Operator` whose `value` is the canonical form, with the
+    // original in `raw`. Symbolic spellings must also be in `operators`
+    // to be lexed as one token.
+    pub operator_aliases: HashMap<String, String>,
+}
+
+// Builder returned by `ParserConfig::builder`. Each method sets one field;
+// fields without a method keep their default.
+#[derive(Debug, Clone)]
+pub struct ParserConfigBuilder {
+    config: ParserConfig,
+}
+
+impl ParserConfigBuilder {
+    // Replaces the keyword set.
+    pub fn keywords<I: IntoIterator<Item = S>, S: Into<String>>(mut self, keywords: I) -> Self {
+        self.config.keywords = keywords.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn keyword(mut self, keyword: impl Into<String>) -> Self {
+        self.config.keywords.insert(keyword.into());
+        self
+    }
+
+    // Replaces the operator set.
+    pub fn operators<I: IntoIterator<Item = S>, S: Into<String>>(mut self, operators: I) -> Self {
+        self.config.operators = operators.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn operator(mut self, operator: impl Into<String>) -> Self {
+        self.config.operators.insert(operator.into());
+        self
+    }
+
+    pub fn line_comment(mut self, marker: impl Into<String>) -> Self {
+        self.config.line_comment = Some(marker.into());
+        self
+    }
+
+    pub fn keep_comments(mut self, keep: bool) -> Self {
+        self.config.keep_comments = keep;
+        self
+    }
+
+    pub fn string_quotes(mut self, quotes: impl IntoIterator<Item = char>) -> Self {
+        self.config.string_quotes = quotes.into_iter().collect();
+        self
+    }
+
+    pub fn recognize_booleans(mut self, enabled: bool) -> Self {
+        self.config.recognize_booleans = enabled;
+        self
+    }
+
+    pub fn track_positions(mut self, enabled: bool) -> Self {
+        self.config.track_positions = enabled;
+        self
+    }
+
+    pub fn emit_eof(mut self, enabled: bool) -> Self {
+        self.config.emit_eof = enabled;
+        self
+    }
+
+    pub fn lossless(mut self, enabled: bool) -> Self {
+        self.config.lossless = enabled;
+        self
+    }
+
+    pub fn build(self) -> ParserConfig {
+        self.config
+    }
+}
+
+// One way two configs differ, as reported by `ParserConfig::diff`. Set
+// changes list, sorted, what only the second config has as `added` and
+// what only the first has as `removed`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigDifference {
+    Keywords {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    Operators {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    Flag {
+        name: &'static str,
+        left: bool,
+        right: bool,
+    },
+    // Any other setting, with both values in `Debug` form.
+    Setting {
+        name: &'static str,
+        left: String,
+        right: String,
+    },
+}
+
+// A letter or `_` followed by any number of letters, digits and `_`.
+fn is_identifier(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// Sorted entries only in `right` and only in `left`, or `None` if the sets
+// are equal.
+fn set_changes(
+    left: &HashSet<String>,
+    right: &HashSet<String>,
+) -> Option<(Vec<String>, Vec<String>)> {
+    if left == right {
+        return None;
+    }
+    let sorted = |a: &HashSet<String>, b: &HashSet<String>| {
+        a.difference(b)
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    };
+    Some((sorted(right, left), sorted(left, right)))
+}
+
+// Presets accepted by `ParserConfig::for_language`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    C,
+    Python,
+    Json,
+    Sql,
+}
+
+// Field splitting for CSV-like inputs: each line is cut at `separator`, and
+// `escape` makes the following character literal (so `a\,b` is one field).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DelimitedMode {
+    pub separator: char,
+    pub escape: char,
+}
+
+// Operators recognized out of the box: arithmetic, assignment, comparison
+// and logical. Every parser built on the default config consults this table.
+pub const DEFAULT_OPERATORS: &[&str] = &[
+    "+", "-", "*", "/", "=", "<", ">", "!", "==", "!=", "<=", ">=", "&&", "||",
+];
+
+// Defaults reproduce the original hardcoded classifier: `if`/`else`/`while`
+// keywords and no comment handling, plus the `DEFAULT_OPERATORS` table.
+// Identifiers are a letter or `_` followed by letters, digits and `_`.
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig {
+            keywords: ["if", "else", "while"]
+                .iter()
+                .map(|k| k.to_string())
+                .collect(),
+            operators: string_set(DEFAULT_OPERATORS),
+            recognize_booleans: false,
+            check_integer_bounds: false,
+            delimited: None,
+            max_identifier_length: None,
+            classification_cache_size: None,
+            line_comment: None,
+            keep_comments: true,
+            sigils: HashSet::new(),
+            attach_sigils: false,
+            radix_prefixes: HashMap::from([
+                ("0x".to_string(), 16),
+                ("0X".to_string(), 16),
+                ("0b".to_string(), 2),
+                ("0B".to_string(), 2),
+            ]),
+            allow_edge_dot_floats: true,
+            float_tokens: false,
+            string_quotes: HashSet::from(['"']),
+            group_punctuation: false,
+            heredocs: false,
+            string_continuation: false,
+            track_positions: true,
+            underscore_insensitive_keywords: false,
+            doc_comment: None,
+            doc_block_comment: None,
+            skip_unrecognized: false,
+            unknown_tokens: false,
+            unicode_line_separators: false,
+            normalize_newlines: false,
+            raw_identifier_prefix: None,
+            keyword_phrases: Vec::new(),
+            emit_eof: false,
+            lossless: false,
+            operator_aliases: HashMap::new(),
+        }
+    }
+}
+
+fn string_set(items: &[&str]) -> HashSet<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+// Builds a `ParserConfig` from literal keyword and operator lists, leaving
+// every other field at its default:
+// `token_table!(keywords: ["if", "else"], operators: ["+", "=="])`.
+#[macro_export]
+macro_rules! token_table {
+    (keywords: [$($kw:expr),* $(,)?], operators: [$($op:expr),* $(,)?] $(,)?) => {
+        $crate::ParserConfig {
+            keywords: [$($kw),*].iter().map(|k: &&str| k.to_string()).collect(),
+            operators: [$($op),*].iter().map(|o: &&str| o.to_string()).collect(),
+            ..$crate::ParserConfig::default()
+        }
+    };
+}
+
+impl ParserConfig {
+    // Fluent alternative to struct-update syntax, starting from the default:
+    // `ParserConfig::builder().keyword("fn").line_comment("#").build()`.
+    pub fn builder() -> ParserConfigBuilder {
+        ParserConfigBuilder {
+            config: ParserConfig::default(),
+        }
+    }
+
+    // Keywords, operators, comment marker, quotes and number prefixes for a
+    // common language. Keyword matching stays case-sensitive, so the SQL
+    // preset lists both upper- and lowercase spellings.
+    pub fn for_language(lang: Language) -> Self {
+        let base = ParserConfig::default();
+        match lang {
+            Language::C => ParserConfig {
+                keywords: string_set(&[
+                    "if", "else", "while", "for", "do", "switch", "case", "default", "break",
+                    "continue", "return", "goto", "struct", "union", "enum", "typedef", "const",
+                    "static", "extern", "sizeof", "void", "char", "int", "long", "short", "float",
+                    "double", "unsigned", "signed",
+                ]),
+                operators: string_set(&[
+                    "+", "-", "*", "/", "%", "=", "==", "!=", "<", ">", "<=", ">=", "&&", "||",
+                    "!", "&", "|", "^", "~", "<<", ">>", "++", "--", "->", "+=", "-=", "*=", "/=",
+                ]),
+                recognize_booleans: true,
+                line_comment: Some("//".to_string()),
+                radix_prefixes: HashMap::from([("0x".to_string(), 16), ("0b".to_string(), 2)]),
+                ..base
+            },
+            Language::Python => ParserConfig {
+                keywords: string_set(&[
+                    "def", "class", "if", "elif", "else", "while", "for", "in", "return", "import",
+                    "from", "as", "pass", "break", "continue", "lambda", "with", "try", "except",
+                    "finally", "raise", "yield", "and", "or", "not", "is", "None", "True", "False",
+                ]),
+                operators: string_set(&[
+                    "+", "-", "*", "/", "//", "%", "**", "=", "==", "!=", "<", ">", "<=", ">=",
+                    "+=", "-=", "*=", "/=", "->",
+                ]),
+                line_comment: Some("#".to_string()),
+                string_quotes: HashSet::from(['"', '\'']),
+                radix_prefixes: HashMap::from([
+                    ("0x".to_string(), 16),
+                    ("0o".to_string(), 8),
+                    ("0b".to_string(), 2),
+                ]),
+                ..base
+            },
+            Language::Json => ParserConfig {
+                keywords: string_set(&["null"]),
+                operators: HashSet::new(),
+                recognize_booleans: true,
+                ..base
+            },
+            Language::Sql => {
+                let keywords = [
+                    "SELECT", "FROM", "WHERE", "INSERT", "INTO", "VALUES", "UPDATE", "SET",
+                    "DELETE", "CREATE", "TABLE", "DROP", "JOIN", "ON", "AND", "OR", "NOT", "NULL",
+                    "AS", "ORDER", "GROUP", "BY",
+                ];
+                ParserConfig {
+                    keywords: keywords
+                        .iter()
+                        .flat_map(|k| [k.to_string(), k.to_lowercase()])
+                        .collect(),
+                    operators: string_set(&[
+                        "+", "-", "*", "/", "=", "<>", "!=", "<", ">", "<=", ">=", "||",
+                    ]),
+                    recognize_booleans: true,
+                    line_comment: Some("--".to_string()),
+                    string_quotes: HashSet::from(['\'']),
+                    ..base
+                }
             }
         }
-        
-        Ok(tokens)
     }
 
-    fn recover_from_error(&self, error: &ParserError) -> Option<RecoveryAction> {
-        match error {
-            ParserError::SyntaxError { line, column, .. } => 
-                Some(RecoveryAction::Synchronize(*line)),
-            ParserError::UnexpectedToken(_) => 
-                Some(RecoveryAction::Skip),
+    // Every setting in which `other` differs from `self`: keyword and
+    // operator set changes first, then boolean flags, then anything else.
+    pub fn diff(&self, other: &ParserConfig) -> Vec<ConfigDifference> {
+        let mut diffs = Vec::new();
+        if let Some((added, removed)) = set_changes(&self.keywords, &other.keywords) {
+            diffs.push(ConfigDifference::Keywords { added, removed });
+        }
+        if let Some((added, removed)) = set_changes(&self.operators, &other.operators) {
+            diffs.push(ConfigDifference::Operators { added, removed });
+        }
+
+        macro_rules! flags {
+            ($($field:ident),*) => {$(
+                if self.$field != other.$field {
+                    diffs.push(ConfigDifference::Flag {
+                        name: stringify!($field),
+                        left: self.$field,
+                        right: other.$field,
+                    });
+                }
+            )*};
+        }
+        flags!(
+            recognize_booleans,
+            check_integer_bounds,
+            keep_comments,
+            attach_sigils,
+            allow_edge_dot_floats,
+            float_tokens,
+            group_punctuation,
+            heredocs,
+            string_continuation,
+            track_positions,
+            underscore_insensitive_keywords,
+            skip_unrecognized,
+            unknown_tokens,
+            unicode_line_separators,
+            normalize_newlines,
+            emit_eof,
+            lossless
+        );
+
+        // Hashed collections are shown sorted so the output is stable.
+        macro_rules! settings {
+            ($($field:ident => $show:expr),*) => {$(
+                if self.$field != other.$field {
+                    let show = $show;
+                    diffs.push(ConfigDifference::Setting {
+                        name: stringify!($field),
+                        left: format!("{:?}", show(&self.$field)),
+                        right: format!("{:?}", show(&other.$field)),
+                    });
+                }
+            )*};
         }
+        settings!(
+            delimited => |v| v,
+            max_identifier_length => |v| v,
+            classification_cache_size => |v| v,
+            line_comment => |v| v,
+            sigils => |v: &HashSet<char>| v.iter().copied().collect::<BTreeSet<_>>(),
+            radix_prefixes => |v: &HashMap<String, u32>| {
+                v.clone().into_iter().collect::<BTreeMap<_, _>>()
+            },
+            string_quotes => |v: &HashSet<char>| v.iter().copied().collect::<BTreeSet<_>>(),
+            doc_comment => |v| v,
+            doc_block_comment => |v| v,
+            raw_identifier_prefix => |v| v,
+            keyword_phrases => |v| v,
+            operator_aliases => |v: &HashMap<String, String>| {
+                v.clone().into_iter().collect::<BTreeMap<_, _>>()
+            }
+        );
+        diffs
     }
-}
 
-impl MinimalParser {
-    fn new() -> Self {
-        MinimalParser {}
+    // Length in bytes of the longest registered operator.
+    pub fn max_operator_len(&self) -> usize {
+        self.operators.iter().map(String::len).max().unwrap_or(0)
+    }
+
+    // Splits a prefixed number like `$FF` into its base and digits, using the
+    // longest matching registered prefix. The digits must be non-empty and
+    // valid in that base.
+    pub fn radix_literal<'t>(&self, token: &'t str) -> Option<(u32, &'t str)> {
+        self.radix_prefixes
+            .iter()
+            .filter(|(prefix, base)| (2..=36).contains(*base) && token.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(prefix, &base)| (base, &token[prefix.len()..]))
+            .filter(|(base, digits)| {
+                !digits.is_empty() && digits.chars().all(|c| c.is_digit(*base))
+            })
     }
 
-    fn classify_token(&self, token: &str) -> TokenType {
+    pub fn classify(&self, token: &str) -> TokenType {
         // Very basic token classification
         match token {
-            "+" | "-" | "*" | "/" => TokenType::Operator,
-            "if" | "else" | "while" => TokenType::Keyword,
-            _ if token.chars().all(char::is_alphabetic) => TokenType::Identifier,
+            _ if self.operators.contains(token) => TokenType::Operator,
+            _ if self.operator_aliases.contains_key(token) => TokenType::Operator,
+            _ if self.radix_literal(token).is_some() => TokenType::Literal,
+            "true" | "false" if self.recognize_booleans => TokenType::Boolean,
+            _ if self.is_keyword(token) => TokenType::Keyword,
+            _ if is_identifier(token) => TokenType::Identifier,
             _ if token.chars().all(char::is_numeric) => TokenType::Literal,
-            _ => TokenType::Punctuation,
+            _ => self.number_kind(token).unwrap_or(TokenType::Punctuation),
+        }
+    }
+
+    // Numbers with an optional leading sign: integers are `Literal`, and so
+    // are decimals unless `float_tokens` makes them `Float`. The lexer treats
+    // `-` and `+` as operators, so signed spellings only reach here when
+    // classified directly. Anything with more than one dot, like `3.14.15`,
+    // is not a number.
+    fn number_kind(&self, token: &str) -> Option<TokenType> {
+        let unsigned = token.strip_prefix(['-', '+']).unwrap_or(token);
+        if !unsigned.is_empty() && unsigned.bytes().all(|b| b.is_ascii_digit()) {
+            Some(TokenType::Literal)
+        } else if self.is_float(unsigned) {
+            Some(if self.float_tokens {
+                TokenType::Float
+            } else {
+                TokenType::Literal
+            })
+        } else {
+            None
+        }
+    }
+
+    // True for a decimal number with an optional sign, such as `-3.14`,
+    // whichever kind `float_tokens` gives it.
+    pub fn is_float_literal(&self, token: &str) -> bool {
+        self.is_float(token.strip_prefix(['-', '+']).unwrap_or(token))
+    }
+
+    fn is_keyword(&self, token: &str) -> bool {
+        self.keywords.contains(token)
+            || (self.underscore_insensitive_keywords
+                && token.contains('_')
+                && self.keywords.contains(&token.replace('_', "")))
+    }
+
+    // Decimal digits around a single dot. Both sides must be present unless
+    // `allow_edge_dot_floats` is set, in which case one may be empty.
+    fn is_float(&self, token: &str) -> bool {
+        let Some((int, frac)) = token.split_once('.') else {
+            return false;
+        };
+        let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if !digits(int) || !digits(frac) {
+            return false;
+        }
+        match (int.is_empty(), frac.is_empty()) {
+            (false, false) => true,
+            (true, true) => false,
+            _ => self.allow_edge_dot_floats,
         }
     }
 }
 
-// Performance Benchmarking Stub
-fn benchmark_parser(parser: &dyn Parser, inputs: &[&str]) {
-    use std::time::Instant;
+// Tokens between calls to a `parse_with_progress_callback` callback.
+pub const PROGRESS_INTERVAL: usize = 256;
 
-    for input in inputs {
-        let start = Instant::now();
-        match parser.parse(input) {
-            Ok(tokens) => {
-                let duration = start.elapsed();
-                println!(
-                    "Parsed {} tokens in {:?}. Input length: {}",
-                    tokens.len(),
-                    duration,
-                    input.len()
-                );
+// Token Classification
+//
+// Decides the kind of each word the lexer cuts out. `ParserConfig` is the
+// built-in implementation; a custom one can be handed to
+// `MinimalParser::with_classifier` for domain-specific kinds.
+pub trait Classifier: Send + Sync {
+    fn classify(&self, word: &str) -> TokenType;
+}
+
+impl Classifier for ParserConfig {
+    fn classify(&self, word: &str) -> TokenType {
+        ParserConfig::classify(self, word)
+    }
+}
+
+// Minimal Parsing Implementation
+pub struct MinimalParser {
+    config: ParserConfig,
+    // Overrides the config's own classification when set.
+    classifier: Option<Box<dyn Classifier>>,
+}
+
+impl Parser for MinimalParser {
+    fn parse(&self, input: &str) -> Result<Vec<Token>, ParserError> {
+        self.tokens(input).collect()
+    }
+
+    // An unmatched quote is a single offending character, so it is skipped
+    // and the rest of its line lexed as code. Other syntax errors cover a
+    // whole word or construct; the rest of their line is dropped.
+    fn recover_from_error(&self, error: &ParserError) -> Option<RecoveryAction> {
+        match error {
+            ParserError::SyntaxError {
+                column, message, ..
+            } if *column > 0 && message.starts_with("unterminated string") => {
+                Some(RecoveryAction::Skip)
             }
-            Err(e) => eprintln!("Parsing error: {}", e),
+            ParserError::SyntaxError { line, .. } => Some(RecoveryAction::Synchronize(*line)),
+            ParserError::UnexpectedToken(_) => Some(RecoveryAction::Skip),
+            ParserError::Io(_) => None,
         }
     }
-}
 
-// Example Usage
-fn main() {
-    let parser = MinimalParser::new();
-    
-    let test_inputs = &[
-        "hello world",
-        "if x + 5 > 10 { do something }",
-        "basic parsing test case",
-    ];
+    fn supported_features(&self) -> FeatureSet {
+        FeatureSet::COMMENTS
+            | FeatureSet::STRINGS
+            | FeatureSet::FLOATS
+            | FeatureSet::SPANS
+            | FeatureSet::RECOVERY
+    }
 
-    benchmark_parser(&parser, test_inputs);
+    // Lexes whole lines until `n` tokens exist, so nothing after the line
+    // holding the `n`th token is read, and errors there go unreported.
+    fn parse_first_n(&self, input: &str, n: usize) -> Result<Vec<Token>, ParserError> {
+        let mut lexer = self.lexer();
+        let source = lexer.source(input);
+        let input = source.as_ref();
+        for (offset, line, line_break) in lines_with_offsets(input) {
+            if lexer.tokens.len() >= n && lexer.heredoc.is_none() {
+                break;
+            }
+            lexer.line_offset = offset;
+            lexer
+                .lex_next_line(line, line_break)
+                .map_err(|error| error.with_snippet(input))?;
+        }
+        if lexer.tokens.len() < n {
+            lexer.finish().map_err(|error| error.with_snippet(input))?;
+        }
+        let mut tokens = lexer.drain_tokens();
+        tokens.truncate(n);
+        Ok(tokens)
+    }
 }
 
-// Unit Tests
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Default for MinimalParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    #[test]
-    fn test_basic_parsing() {
-        let parser = MinimalParser::new();
-        let input = "hello world";
-        
-        let result = parser.parse(input);
-        assert!(result.is_ok());
-        
-        let tokens = result.unwrap();
-        assert_eq!(tokens.len(), 2);
-        assert_eq!(tokens[0].value, "hello");
-        assert_eq!(tokens[1].value, "world");
+impl MinimalParser {
+    pub fn new() -> Self {
+        Self::with_config(ParserConfig::default())
     }
 
-    #[test]
-    fn test_token_classification() {
-        let parser = MinimalParser::new();
-        
-        assert_eq!(parser.classify_token("+"), TokenType::Operator);
-        assert_eq!(parser.classify_token("if"), TokenType::Keyword);
-        assert_eq!(parser.classify_token("variable"), TokenType::Identifier);
-        assert_eq!(parser.classify_token("42"), TokenType::Literal);
+    pub fn with_config(config: ParserConfig) -> Self {
+        MinimalParser {
+            config,
+            classifier: None,
+        }
     }
-}
\ No newline at end of file
+
+    // Parser that classifies words with `classifier` instead of the rules in
+    // `config`, which still drives everything else.
+    pub fn with_classifier(config: ParserConfig, classifier: impl Classifier + 'static) -> Self {
+        MinimalParser {
+            config,
+            classifier: Some(Box::new(classifier)),
+        }
+    }
+
+    fn lexer(&self) -> Lexer<'_> {
+        match &self.classifier {
+            Some(classifier) => Lexer::with_classifier(&self.config, classifier.as_ref()),
+            None => Lexer::new(&self.config),
+        }
+    }
+
+    // Same as `with_config`; reads better at the end of a builder chain.
+    pub fn from_config(config: ParserConfig) -> Self {
+        Self::with_config(config)
+    }
+
+    // Default configuration with `keywords` replacing the built-in
+    // `if`/`else`/`while` set.
+    pub fn with_keywords(keywords: impl IntoIterator<Item = String>) -> Self {
+        Self::with_config(ParserConfig {
+            keywords: keywords.into_iter().collect(),
+            ..ParserConfig::default()
+        })
+    }
+
+    // Default configuration that drops everything from `prefix` to the end
+    // of a line, e.g. `#` comments. A prefix inside a string literal is part
+    // of the string.
+    pub fn with_comment_prefix(prefix: &str) -> Self {
+        Self::with_config(ParserConfig {
+            line_comment: Some(prefix.to_string()),
+            keep_comments: false,
+            ..ParserConfig::default()
+        })
+    }
+
+    pub fn classify_token(&self, token: &str) -> TokenType {
+        match &self.classifier {
+            Some(classifier) => classifier.classify(token),
+            None => self.config.classify(token),
+        }
+    }
+
+    // True when `input` lexes to nothing but trivia (see
+    // `TokenType::is_trivia`), so anything the lexer drops, such as block
+    // doc comments or skipped noise, counts as trivial too. Lexes a line at
+    // a time, stops at the first significant token, and keeps no tokens
+    // from earlier lines, so it is cheap enough to run over every file in a
+    // tree. Input that fails to lex is not trivial.
+    pub fn is_trivial(&self, input: &str) -> bool {
+        let mut lexer = self.lexer();
+        let source = lexer.source(input);
+        let input = source.as_ref();
+        let significant = |lexer: &Lexer| lexer.tokens.iter().any(|t| !t.kind.is_trivia());
+        for (offset, line, line_break) in lines_with_offsets(input) {
+            lexer.line_offset = offset;
+            if lexer.lex_next_line(line, line_break).is_err() || significant(&lexer) {
+                return false;
+            }
+            lexer.tokens.clear();
+        }
+        lexer.finish().is_ok() && !significant(&lexer)
+    }
+
+    // Parses `input`, also reporting how many bytes were processed. On
+    // failure the count is the byte offset of the token that was rejected.
+    pub fn parse_with_progress(&self, input: &str) -> (Result<Vec<Token>, ParserError>, usize) {
+        let mut lexer = self.lexer();
+        let result = lexer.lex(input);
+        let consumed = lexer.bytes_consumed();
+        (result.map(|()| lexer.drain_tokens()), consumed)
+    }
+
+    // Parses `input`, also counting how often each scanner ran. Counts cover
+    // what was lexed before a failure.
+    pub fn parse_with_metrics(
+        &self,
+        input: &str,
+    ) -> (Result<Vec<Token>, ParserError>, ScanMetrics) {
+        let mut lexer = self.lexer();
+        let result = lexer.lex(input).map(|()| lexer.drain_tokens());
+        (result, lexer.scans)
+    }
+
+    // Parses `input`, calling `cb` with the fraction of bytes consumed after
+    // every `PROGRESS_INTERVAL` tokens and with `1.0` once parsing succeeds.
+    // Reported fractions never decrease.
+    pub fn parse_with_progress_callback<F: FnMut(f64)>(
+        &self,
+        input: &str,
+        mut cb: F,
+    ) -> Result<Vec<Token>, ParserError> {
+        let total = input.len().max(1) as f64;
+        let mut consumed = 0;
+        let mut tokens = Vec::new();
+        for token in self.tokens(input) {
+            let token = token?;
+            consumed = consumed.max(token.span.end);
+            tokens.push(token);
+            if tokens.len() % PROGRESS_INTERVAL == 0 {
+                cb(consumed as f64 / total);
+            }
+        }
+        cb(1.0);
+        Ok(tokens)
+    }
+
+    // Parses `input`, failing with a `SyntaxError` at the end of the input
+    // when fewer than `min` tokens come out. Lexing errors take precedence.
+    pub fn require_min_tokens(&self, input: &str, min: usize) -> Result<Vec<Token>, ParserError> {
+        let tokens = self.parse(input)?;
+        if tokens.len() < min {
+            let (line, column) = SourceBuffer::new(input)
+                .offset_to_position(input.len())
+                .unwrap_or((0, 0));
+            return Err(ParserError::SyntaxError {
+                line,
+                column,
+                message: format!("expected at least {} tokens, found {}", min, tokens.len()),
+                snippet: String::new(),
+            });
+        }
+        Ok(tokens)
+    }
+
+    // Lexes `input` a line at a time and stops at the first line holding a
+    // `kind` token. Lines after that are never scanned, so errors there are
+    // not reported.
+    pub fn contains_kind(&self, input: &str, kind: TokenType) -> Result<bool, ParserError> {
+        let mut lexer = self.lexer();
+        let source = lexer.source(input);
+        let input = source.as_ref();
+        for (offset, line, line_break) in lines_with_offsets(input) {
+            lexer.line_offset = offset;
+            lexer
+                .lex_next_line(line, line_break)
+                .map_err(|error| error.with_snippet(input))?;
+            if lexer.tokens.iter().any(|token| token.kind == kind) {
+                return Ok(true);
+            }
+            lexer.tokens.clear();
+        }
+        lexer.finish().map_err(|error| error.with_snippet(input))?;
+        Ok(false)
+    }
+
+    // Lexes only `input[range]`, reporting lines, columns and spans as they
+    // are in the whole of `input`, including in errors.
+    pub fn parse_range(&self, input: &str, range: Range<usize>) -> Result<Vec<Token>, ParserError> {
+        let Some(slice) = input.get(range.clone()) else {
+            return Err(ParserError::SyntaxError {
+                line: 0,
+                column: 0,
+                message: format!("range {:?} is out of bounds or splits a character", range),
+                snippet: String::new(),
+            });
+        };
+        let (first_line, first_column) = SourceBuffer::new(input)
+            .offset_to_position(range.start)
+            .unwrap_or((1, 1));
+        // Only the first line of the slice starts part-way into a line.
+        let shift = |line: &mut usize, column: &mut usize| {
+            if *line == 1 {
+                *column += first_column - 1;
+            }
+            if *line > 0 {
+                *line += first_line - 1;
+            }
+        };
+
+        let mut tokens = self.parse(slice).map_err(|mut error| {
+            if let ParserError::SyntaxError {
+                line,
+                column,
+                snippet,
+                ..
+            } = &mut error
+            {
+                shift(line, column);
+                snippet.clear();
+            }
+            error.with_snippet(input)
+        })?;
+        for token in &mut tokens {
+            shift(&mut token.line, &mut token.column);
+            token.span = Span::new(token.span.start + range.start, token.span.end + range.start);
+        }
+        Ok(tokens)
+    }
+
+    // Parses `input`, asking `recover_from_error` how to continue after each
+    // error instead of stopping, and logs every error with the action taken.
+    // Recovery is as in `parse_resilient`. Fails at the first error for which
+    // no action is offered.
+    pub fn parse_recovering(&self, input: &str) -> Result<(Vec<Token>, RecoveryLog), ParserError> {
+        let (tokens, recovered) = self.recover_all(input);
+        let mut log = RecoveryLog::default();
+        for (error, action) in recovered {
+            match action {
+                Some(action) => log.entries.push((error, action)),
+                None => return Err(error),
+            }
+        }
+        Ok((tokens, log))
+    }
+
+    // Parses `input` to the end no matter what, collecting every error and
+    // honoring `recover_from_error` for each: `Skip` drops the character the
+    // error points at and lexes on from the next one, `Replace` adds its
+    // token and `Synchronize` resumes at the start of its line. A
+    // `Synchronize` to the current or an earlier line, no action at all, or
+    // a `Skip` without a position on this line drops the rest of the line.
+    pub fn parse_resilient(&self, input: &str) -> (Vec<Token>, Vec<ParserError>) {
+        let (tokens, recovered) = self.recover_all(input);
+        (
+            tokens,
+            recovered.into_iter().map(|(error, _)| error).collect(),
+        )
+    }
+
+    // Shared loop behind `parse_recovering` and `parse_resilient`: every
+    // error in order, with the action that was applied to it, if any.
+    fn recover_all(&self, input: &str) -> (Vec<Token>, Vec<(ParserError, Option<RecoveryAction>)>) {
+        let mut lexer = self.lexer();
+        let source = lexer.source(input);
+        let input = source.as_ref();
+        let mut recovered = Vec::new();
+        let mut resume_at = 0;
+
+        for (offset, line, line_break) in lines_with_offsets(input) {
+            if lexer.line + 1 < resume_at {
+                lexer.line += 1;
+                continue;
+            }
+            let line_no = lexer.line + 1;
+            lexer.line_offset = offset;
+            let mut result = lexer.lex_next_line(line, line_break);
+            while let Err(error) = result {
+                let action = self.recover_from_error(&error);
+                // Byte offset and column just past the offending character.
+                let skip_to = match (&action, &error) {
+                    (
+                        Some(RecoveryAction::Skip),
+                        ParserError::SyntaxError {
+                            line: l, column, ..
+                        },
+                    ) if *l == line_no && lexer.line == line_no && *column > 0 => {
+                        let offending = line.char_indices().nth(column - 1);
+                        offending.map(|(at, c)| (at + c.len_utf8(), column + 1))
+                    }
+                    _ => None,
+                };
+                match &action {
+                    Some(RecoveryAction::Replace(token)) => lexer.tokens.push(token.clone()),
+                    Some(RecoveryAction::Synchronize(line)) => resume_at = *line,
+                    _ => {}
+                }
+                recovered.push((error.with_snippet(input), action));
+                result = match skip_to {
+                    Some((next, column)) => {
+                        lexer.line_offset = offset + next;
+                        lexer.lex_line(&line[next..], column).map(|_| ())
+                    }
+                    None => Ok(()),
+                };
+            }
+        }
+        if let Err(error) = lexer.finish() {
+            let action = self.recover_from_error(&error);
+            recovered.push((error.with_snippet(input), action));
+        }
+
+        (lexer.drain_tokens(), recovered)
+    }
+
+    // Takes ownership of `input` and returns it bundled with its tokens, so
+    // the result can be moved around without borrowing from the caller.
+    pub fn parse_owned(&self, input: String) -> Result<OwnedTokenStream, ParserError> {
+        // Spans index the normalized text, so that is the source to keep.
+        let input = if self.config.normalize_newlines && input.contains('\r') {
+            normalize_newlines(&input).into_owned()
+        } else {
+            input
+        };
+        let tokens = self
+            .parse(&input)?
+            .into_iter()
+            .map(|token| SourceToken {
+                kind: token.kind,
+                span: token.span,
+                line: token.line,
+                column: token.column,
+            })
+            .collect();
+        Ok(OwnedTokenStream {
+            source: input,
+            tokens,
+        })
+    }
+
+    // Yields tokens one at a time, lexing the next line only once the tokens
+    // before it have been taken, so abandoning the iterator early skips the
+    // rest of the input. An error ends the iteration. `parse` collects this.
+    pub fn tokens<'a>(
+        &'a self,
+        input: &'a str,
+    ) -> impl Iterator<Item = Result<Token, ParserError>> + 'a {
+        let mut lexer = self.lexer();
+        let source = lexer.source(input);
+        let mut next_line = 0;
+        let mut ready = VecDeque::new();
+        let mut done = false;
+        let mut gaps = self.config.lossless.then(|| GapFiller::new(&self.config));
+        std::iter::from_fn(move || loop {
+            let input = source.as_ref();
+            if let Some(token) = ready.pop_front() {
+                return Some(Ok(token));
+            }
+            if done {
+                return None;
+            }
+            let result = match line_at(input, next_line) {
+                Some((line, line_break)) => {
+                    lexer.line_offset = next_line;
+                    next_line += line.len() + line_break.len();
+                    lexer.lex_next_line(line, line_break)
+                }
+                None => {
+                    done = true;
+                    lexer.finish()
+                }
+            };
+            if let Err(error) = result {
+                done = true;
+                return Some(Err(error.with_snippet(input)));
+            }
+            if done && self.config.emit_eof {
+                lexer.tokens.push(self.eof_token(input));
+            }
+            // A heredoc or block doc comment is slotted in among earlier
+            // tokens when it closes, so hold tokens back while one is open.
+            if lexer.heredoc.is_none() && lexer.doc_block.is_none() {
+                match gaps.as_mut() {
+                    Some(gaps) => {
+                        for token in lexer.tokens.drain(..) {
+                            gaps.push(input, token, &mut ready);
+                        }
+                        if done {
+                            gaps.fill_to(input, input.len(), &mut ready);
+                        }
+                    }
+                    None => ready.extend(lexer.tokens.drain(..)),
+                }
+            }
+        })
+    }
+
+    fn eof_token(&self, input: &str) -> Token {
+        let (line, column) = if self.config.track_positions {
+            SourceBuffer::new(input)
+                .offset_to_position(input.len())
+                .unwrap_or((0, 0))
+        } else {
+            (0, 0)
+        };
+        Token {
+            kind: TokenType::Eof,
+            value: String::new(),
+            line,
+            column,
+            raw: None,
+            span: Span::new(input.len(), input.len()),
+            payload: (),
+        }
+    }
+
+    // Lazily tokenizes `reader` one line at a time, yielding each line's
+    // tokens as soon as it has been read. Line numbers keep counting across
+    // items, so the stream matches what `parse` would report for the whole text.
+    pub fn tokenize_reader_lines<'a, R: BufRead + 'a>(
+        &'a self,
+        reader: R,
+    ) -> impl Iterator<Item = Result<Vec<Token>, ParserError>> + 'a {
+        let mut lexer = self.lexer();
+        let mut reader = reader;
+        let mut buf = String::new();
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            buf.clear();
+            match reader.read_line(&mut buf) {
+                Ok(0) => return lexer.finish().err().map(Err),
+                Ok(_) => {}
+                Err(e) => return Some(Err(ParserError::Io(e))),
+            }
+            // What was read ends at `\n`, but may hold lines ended by a lone `\r`.
+            let source = lexer.source(&buf);
+            for (start, line, line_break) in lines_with_offsets(&source) {
+                lexer.line_offset = offset + start;
+                if let Err(error) = lexer.lex_next_line(line, line_break) {
+                    return Some(Err(error));
+                }
+            }
+            offset += source.len();
+            Some(Ok(std::mem::take(&mut lexer.tokens)))
+        })
+    }
+}
+
+// Owned Token Stream
+//
+// A source string together with span-only tokens; token text is sliced out
+// of the source on demand rather than copied into each token. For strings
+// and comments the text is the full source spelling, quotes and marker
+// included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceToken {
+    pub kind: TokenType,
+    pub span: Span,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct OwnedTokenStream {
+    source: String,
+    tokens: Vec<SourceToken>,
+}
+
+impl OwnedTokenStream {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tokens(&self) -> &[SourceToken] {
+        &self.tokens
+    }
+
+    pub fn text(&self, token: &SourceToken) -> &str {
+        &self.source[token.span.start..token.span.end]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SourceToken, &str)> {
+        self.tokens
+            .iter()
+            .map(move |token| (token, self.text(token)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn into_source(self) -> String {
+        self.source
+    }
+}
+
+// Analysis Result
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMetrics {
+    pub bytes: usize,
+    pub lines: usize,
+    pub blank_lines: usize,
+    pub tokens: usize,
+}
+
+// Everything `MinimalParser::analyze` learned about an input in one value.
+#[derive(Debug)]
+pub struct ParseResult {
+    pub tokens: Vec<Token>,
+    pub diagnostics: Vec<ParserError>,
+    pub metrics: SourceMetrics,
+}
+
+impl MinimalParser {
+    // Lexes all of `input` without stopping at the first error: a line that
+    // fails contributes the tokens before the error plus one diagnostic, and
+    // lexing resumes on the next line.
+    pub fn analyze(&self, input: &str) -> ParseResult {
+        let mut lexer = self.lexer();
+        let source = lexer.source(input);
+        let input = source.as_ref();
+        let mut diagnostics = Vec::new();
+        let mut metrics = SourceMetrics {
+            bytes: input.len(),
+            ..SourceMetrics::default()
+        };
+
+        for (offset, line, line_break) in lines_with_offsets(input) {
+            metrics.lines += 1;
+            if line.trim().is_empty() {
+                metrics.blank_lines += 1;
+            }
+            lexer.line_offset = offset;
+            if let Err(error) = lexer.lex_next_line(line, line_break) {
+                diagnostics.push(error.with_snippet(input));
+            }
+        }
+        if let Err(error) = lexer.finish() {
+            diagnostics.push(error.with_snippet(input));
+        }
+
+        let tokens = lexer.drain_tokens();
+        metrics.tokens = tokens.len();
+        ParseResult {
+            tokens,
+            diagnostics,
+            metrics,
+        }
+    }
+}
+
+// JSON Lexer
+//
+// Tokenizes JSON text: structural characters become `Punctuation`, strings
+// `StringLiteral`, numbers `Literal`, `true`/`false` `Boolean` and `null` a
+// `Keyword`. Anything else is an `UnexpectedToken`.
+#[derive(Debug, Default)]
+pub struct JsonLexer;
+
+impl JsonLexer {
+    pub fn new() -> Self {
+        JsonLexer
+    }
+
+    fn number_end(line: &str, start: usize) -> usize {
+        line[start..]
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .map_or(line.len(), |len| start + len)
+    }
+
+    // Decodes the string opening at `start` with the escapes RFC 8259
+    // allows and no others; a `\u` surrogate pair becomes one character.
+    // Returns the value and the offset past the closing quote, `None` if the
+    // line ends first, or the offset of the backslash of an invalid escape.
+    fn decode_string(line: &str, start: usize) -> Result<Option<(String, usize)>, usize> {
+        let body = start + 1;
+        let mut value = String::new();
+        let mut chars = line[body..].char_indices();
+        let hex4 = |chars: &mut std::str::CharIndices| {
+            (0..4).try_fold(0, |unit, _| Some(unit * 16 + chars.next()?.1.to_digit(16)?))
+        };
+
+        while let Some((i, c)) = chars.next() {
+            if c == '"' {
+                return Ok(Some((value, body + i + 1)));
+            }
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            let escape = body + i;
+            let decoded = match chars.next().map(|(_, e)| e) {
+                Some(e @ ('"' | '\\' | '/')) => e,
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('u') => {
+                    let unit = hex4(&mut chars).ok_or(escape)?;
+                    let unit = match unit {
+                        0xD800..=0xDBFF => {
+                            let low = match (chars.next(), chars.next()) {
+                                (Some((_, '\\')), Some((_, 'u'))) => hex4(&mut chars),
+                                _ => None,
+                            };
+                            match low {
+                                Some(low @ 0xDC00..=0xDFFF) => {
+                                    0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+                                }
+                                _ => return Err(escape),
+                            }
+                        }
+                        unit => unit,
+                    };
+                    char::from_u32(unit).ok_or(escape)?
+                }
+                None => return Ok(None),
+                Some(_) => return Err(escape),
+            };
+            value.push(decoded);
+        }
+
+        Ok(None)
+    }
+
+    fn is_json_number(text: &str) -> bool {
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        let (mantissa, exponent) = match digits.find(['e', 'E']) {
+            Some(at) => (&digits[..at], Some(&digits[at + 1..])),
+            None => (digits, None),
+        };
+        let (int, frac) = match mantissa.split_once('.') {
+            Some((int, frac)) => (int, Some(frac)),
+            None => (mantissa, None),
+        };
+        let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+        all_digits(int)
+            && (int == "0" || !int.starts_with('0'))
+            && frac.is_none_or(all_digits)
+            && exponent.is_none_or(|e| all_digits(e.strip_prefix(['+', '-']).unwrap_or(e)))
+    }
+}
+
+impl Parser for JsonLexer {
+    fn parse(&self, input: &str) -> Result<Vec<Token>, ParserError> {
+        let mut tokens = Vec::new();
+
+        for (line_index, (offset, line, _)) in lines_with_offsets(input).enumerate() {
+            let line_num = line_index + 1;
+            let mut col = 1;
+            let mut pos = 0;
+
+            while let Some(c) = line[pos..].chars().next() {
+                if c.is_whitespace() {
+                    pos += c.len_utf8();
+                    col += 1;
+                    continue;
+                }
+
+                let start = pos;
+                let (kind, value, raw) = match c {
+                    '{' | '}' | '[' | ']' | ':' | ',' => {
+                        pos += 1;
+                        (TokenType::Punctuation, c.to_string(), None)
+                    }
+                    '"' => {
+                        let error = |column: usize, message: &str| ParserError::SyntaxError {
+                            line: line_num,
+                            column,
+                            message: message.to_string(),
+                            snippet: line.to_string(),
+                        };
+                        let (value, end) = match Self::decode_string(line, start) {
+                            Ok(Some(decoded)) => decoded,
+                            Ok(None) => return Err(error(col, "unterminated string literal")),
+                            Err(at) => {
+                                let column = col + line[start..at].chars().count();
+                                return Err(error(column, "invalid escape in string literal"));
+                            }
+                        };
+                        pos = end;
+                        (
+                            TokenType::StringLiteral,
+                            value,
+                            Some(line[start..end].to_string()),
+                        )
+                    }
+                    '-' | '0'..='9' => {
+                        pos = Self::number_end(line, start);
+                        let text = &line[start..pos];
+                        if !Self::is_json_number(text) {
+                            return Err(ParserError::UnexpectedToken(text.to_string()));
+                        }
+                        (TokenType::Literal, text.to_string(), None)
+                    }
+                    _ => {
+                        pos = line[start..]
+                            .find(|c: char| !c.is_ascii_alphabetic())
+                            .map_or(line.len(), |len| start + len)
+                            .max(start + c.len_utf8());
+                        let word = &line[start..pos];
+                        let kind = match word {
+                            "true" | "false" => TokenType::Boolean,
+                            "null" => TokenType::Keyword,
+                            _ => return Err(ParserError::UnexpectedToken(word.to_string())),
+                        };
+                        (kind, word.to_string(), None)
+                    }
+                };
+
+                tokens.push(Token {
+                    kind,
+                    value,
+                    line: line_num,
+                    column: col,
+                    raw,
+                    span: Span::new(offset + start, offset + pos),
+                    payload: (),
+                });
+                col += line[start..pos].chars().count();
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    fn recover_from_error(&self, error: &ParserError) -> Option<RecoveryAction> {
+        match error {
+            ParserError::SyntaxError { line, .. } => Some(RecoveryAction::Synchronize(*line)),
+            ParserError::UnexpectedToken(_) => Some(RecoveryAction::Skip),
+            ParserError::Io(_) => None,
+        }
+    }
+
+    fn supported_features(&self) -> FeatureSet {
+        FeatureSet::STRINGS | FeatureSet::FLOATS | FeatureSet::SPANS | FeatureSet::RECOVERY
+    }
+}
+
+// Arena Allocation
+//
+// Tokens whose text lives in a caller-provided `bumpalo::Bump`, so a whole
+// token stream can be released at once by resetting the arena.
+#[cfg(feature = "bumpalo")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaToken<'bump> {
+    pub kind: TokenType,
+    pub value: &'bump str,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[cfg(feature = "bumpalo")]
+impl MinimalParser {
+    // Lexes line by line, moving each line's tokens into `bump` before the
+    // next one is scanned, so only one line of scratch tokens is live at a time.
+    pub fn parse_in<'bump>(
+        &self,
+        input: &str,
+        bump: &'bump bumpalo::Bump,
+    ) -> Result<bumpalo::collections::Vec<'bump, ArenaToken<'bump>>, ParserError> {
+        let mut lexer = self.lexer();
+        let source = lexer.source(input);
+        let input = source.as_ref();
+        let mut tokens = bumpalo::collections::Vec::new_in(bump);
+
+        for (offset, line, line_break) in lines_with_offsets(input) {
+            lexer.line_offset = offset;
+            lexer
+                .lex_next_line(line, line_break)
+                .map_err(|error| error.with_snippet(input))?;
+            for token in lexer.tokens.drain(..) {
+                tokens.push(ArenaToken {
+                    kind: token.kind,
+                    value: bump.alloc_str(&token.value),
+                    line: token.line,
+                    column: token.column,
+                });
+            }
+        }
+        lexer.finish().map_err(|error| error.with_snippet(input))?;
+
+        Ok(tokens)
+    }
+}
+
+// Binary Serialization
+//
+// Compact token dumps for large inputs. The format is whatever `bincode`
+// produces for `Vec<Token>`, so it is only readable by a matching version.
+#[cfg(feature = "bincode")]
+pub fn to_bincode(tokens: &[Token]) -> Vec<u8> {
+    bincode::serialize(tokens).expect("tokens contain only serializable data")
+}
+
+#[cfg(feature = "bincode")]
+pub fn from_bincode(bytes: &[u8]) -> Result<Vec<Token>, bincode::Error> {
+    bincode::deserialize(bytes)
+}
+
+// Rowan Integration
+//
+// Builds a flat `rowan` green tree: one root node holding every token in
+// order. Token kinds map to `SyntaxKind`s by `TokenType` discriminant, and
+// the root takes the next free kind, `TokenType::COUNT`. Feed it `lossless`
+// output so the tree's text is the source.
+#[cfg(feature = "rowan")]
+pub const ROWAN_ROOT: rowan::SyntaxKind = rowan::SyntaxKind(TokenType::COUNT);
+
+#[cfg(feature = "rowan")]
+pub fn rowan_kind(kind: &TokenType) -> rowan::SyntaxKind {
+    rowan::SyntaxKind(kind.clone() as u16)
+}
+
+#[cfg(feature = "rowan")]
+pub fn build_green_tree(tokens: &[Token]) -> rowan::GreenNode {
+    let mut builder = rowan::GreenNodeBuilder::new();
+    builder.start_node(ROWAN_ROOT);
+    for token in tokens {
+        builder.token(rowan_kind(&token.kind), &token.value);
+    }
+    builder.finish_node();
+    builder.finish()
+}
+
+// Bracket Grouping
+#[derive(Debug, Clone)]
+pub enum TokenTree {
+    Leaf(Token),
+    Group {
+        open: Token,
+        children: Vec<TokenTree>,
+        close: Token,
+    },
+}
+
+fn closing_bracket(open: &str) -> Option<&'static str> {
+    match open {
+        "(" => Some(")"),
+        "[" => Some("]"),
+        "{" => Some("}"),
+        _ => None,
+    }
+}
+
+// Groups bracketed runs into nested trees. Uses an explicit stack rather than
+// recursion, so nesting is bounded by `max_depth` instead of the call stack.
+// Only punctuation tokens count as brackets; a string literal `"("` is a leaf.
+pub fn group_brackets(tokens: &[Token], max_depth: usize) -> Result<Vec<TokenTree>, ParserError> {
+    let mut stack: Vec<(Token, Vec<TokenTree>)> = Vec::new();
+    let mut current = Vec::new();
+
+    for token in tokens {
+        if token.kind == TokenType::Punctuation && closing_bracket(&token.value).is_some() {
+            if stack.len() >= max_depth {
+                return Err(ParserError::SyntaxError {
+                    line: token.line,
+                    column: token.column,
+                    message: "maximum nesting depth exceeded".to_string(),
+                    snippet: String::new(),
+                });
+            }
+            stack.push((token.clone(), std::mem::take(&mut current)));
+            continue;
+        }
+
+        if is_punctuation(token, &[")", "]", "}"]) {
+            let (open, parent) = stack
+                .pop()
+                .ok_or_else(|| ParserError::UnexpectedToken(token.value.clone()))?;
+            if closing_bracket(&open.value) != Some(token.value.as_str()) {
+                return Err(ParserError::SyntaxError {
+                    line: token.line,
+                    column: token.column,
+                    message: format!(
+                        "mismatched delimiter: {} closed by {}",
+                        open.value, token.value
+                    ),
+                    snippet: String::new(),
+                });
+            }
+            let children = std::mem::replace(&mut current, parent);
+            current.push(TokenTree::Group {
+                open,
+                children,
+                close: token.clone(),
+            });
+            continue;
+        }
+
+        current.push(TokenTree::Leaf(token.clone()));
+    }
+
+    if let Some((open, _)) = stack.pop() {
+        return Err(ParserError::SyntaxError {
+            line: open.line,
+            column: open.column,
+            message: format!("unclosed delimiter: {}", open.value),
+            snippet: String::new(),
+        });
+    }
+
+    Ok(current)
+}
+
+// Token Stream Formatting
+//
+// Reassembles tokens into a single line with normalized spacing: binary
+// operators are padded, unary ones hug their operand, separators and closing
+// brackets hug the previous token, and calls keep the callee attached to its
+// opening bracket. Tokens are written as spelled in the source, so strings
+// keep their quotes and comments their markers; a comment followed by more
+// tokens ends the line instead of swallowing them.
+pub fn format_tokens(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut prev: Option<(&Token, bool)> = None;
+
+    for token in tokens {
+        let unary = token.kind == TokenType::Operator
+            && operator_arity(&token.value, operator_context(prev.map(|(t, _)| t))) == Arity::Unary;
+        if let Some((prev, prev_unary)) = prev {
+            if matches!(prev.kind, TokenType::Comment | TokenType::DocComment) {
+                out.push('\n');
+            } else if needs_space(prev, prev_unary, token, unary) {
+                out.push(' ');
+            }
+        }
+        out.push_str(token.source_text());
+        prev = Some((token, unary));
+    }
+
+    out
+}
+
+// An operator is in prefix position when no operand precedes it: at the
+// start, or after an opening bracket, a comma, a keyword or another operator.
+fn operator_context(prev: Option<&Token>) -> OperatorContext {
+    match prev {
+        None => OperatorContext::Prefix,
+        Some(t) if matches!(t.kind, TokenType::Operator | TokenType::Keyword) => {
+            OperatorContext::Prefix
+        }
+        Some(t) if is_punctuation(t, &["(", "[", ","]) => OperatorContext::Prefix,
+        Some(_) => OperatorContext::Infix,
+    }
+}
+
+fn needs_space(prev: &Token, prev_unary: bool, next: &Token, next_unary: bool) -> bool {
+    if prev_unary {
+        return false;
+    }
+    if prev.kind == TokenType::Operator || (next.kind == TokenType::Operator && !next_unary) {
+        return true;
+    }
+    if is_punctuation(next, &[",", ";", ")", "]"]) || is_punctuation(prev, &["(", "["]) {
+        return false;
+    }
+    if is_punctuation(next, &["(", "["]) {
+        return prev.kind != TokenType::Identifier;
+    }
+    true
+}
+
+// True for a punctuation token spelled as one of `marks`; a string literal
+// holding the same text does not count.
+fn is_punctuation(token: &Token, marks: &[&str]) -> bool {
+    token.kind == TokenType::Punctuation && marks.contains(&token.value.as_str())
+}
+
+// Operator Arity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperatorContext {
+    // No operand precedes the operator (start of expression, after `(` or another operator).
+    Prefix,
+    // The operator sits between two operands.
+    Infix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Unary,
+    Binary,
+}
+
+// Resolves how many operands an operator takes where it appears. Sign-like
+// operators switch on context, prefix-only ones such as `!` are always unary,
+// and everything else is binary.
+pub fn operator_arity(value: &str, context: OperatorContext) -> Arity {
+    match (value, context) {
+        ("!" | "~", _) => Arity::Unary,
+        ("+" | "-", OperatorContext::Prefix) => Arity::Unary,
+        _ => Arity::Binary,
+    }
+}
+
+// Lint for strict grammars: two identifiers in a row with nothing between
+// them. Each offending pair yields a `SyntaxError` at the second identifier.
+pub fn check_adjacent_identifiers(tokens: &[Token]) -> Vec<ParserError> {
+    tokens
+        .windows(2)
+        .filter(|pair| pair.iter().all(|t| t.kind == TokenType::Identifier))
+        .map(|pair| ParserError::SyntaxError {
+            line: pair[1].line,
+            column: pair[1].column,
+            message: format!(
+                "unexpected identifier {} after identifier {}",
+                pair[1].value, pair[0].value
+            ),
+            snippet: String::new(),
+        })
+        .collect()
+}
+
+// Indices of operator tokens immediately followed, with no gap in the
+// source, by the same single-character operator, as in `a ++ b` when `++`
+// is not registered. A registered compound would have lexed as one token,
+// so any such pair is a likely typo.
+pub fn find_repeated_operators(tokens: &[Token]) -> Vec<usize> {
+    tokens
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| {
+            let (a, b) = (&pair[0], &pair[1]);
+            a.kind == TokenType::Operator
+                && b.kind == TokenType::Operator
+                && a.value.chars().count() == 1
+                && a.value == b.value
+                && a.span.end == b.span.start
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Token Kind Transitions
+//
+// Which token kinds may directly follow which. A table starts either from
+// nothing allowed (`new`) or everything allowed (`permissive`), and the
+// listed pairs are exceptions to that default.
+#[derive(Debug, Clone, Default)]
+pub struct TransitionTable {
+    allow_by_default: bool,
+    exceptions: HashSet<(TokenType, TokenType)>,
+}
+
+impl TransitionTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn permissive() -> Self {
+        TransitionTable {
+            allow_by_default: true,
+            exceptions: HashSet::new(),
+        }
+    }
+
+    pub fn allow(&mut self, prev: TokenType, next: TokenType) -> &mut Self {
+        self.set(prev, next, true)
+    }
+
+    pub fn forbid(&mut self, prev: TokenType, next: TokenType) -> &mut Self {
+        self.set(prev, next, false)
+    }
+
+    fn set(&mut self, prev: TokenType, next: TokenType, allowed: bool) -> &mut Self {
+        if allowed == self.allow_by_default {
+            self.exceptions.remove(&(prev, next));
+        } else {
+            self.exceptions.insert((prev, next));
+        }
+        self
+    }
+
+    pub fn is_allowed(&self, prev: &TokenType, next: &TokenType) -> bool {
+        self.exceptions.contains(&(prev.clone(), next.clone())) != self.allow_by_default
+    }
+}
+
+// Flags every adjacent pair whose kinds `allowed` rejects, reporting the
+// position of the second token.
+pub fn validate_transitions(tokens: &[Token], allowed: &TransitionTable) -> Vec<ParserError> {
+    tokens
+        .windows(2)
+        .filter(|pair| !allowed.is_allowed(&pair[0].kind, &pair[1].kind))
+        .map(|pair| ParserError::SyntaxError {
+            line: pair[1].line,
+            column: pair[1].column,
+            message: format!(
+                "{} {} may not follow {} {}",
+                pair[1].kind, pair[1].value, pair[0].kind, pair[0].value
+            ),
+            snippet: String::new(),
+        })
+        .collect()
+}
+
+// Indices of `,` tokens whose next significant token is a closing bracket,
+// as in `[1, 2,]`. Whitespace and comment tokens in between are skipped, so
+// lossless output is handled the same as plain output.
+pub fn find_trailing_commas(tokens: &[Token]) -> Vec<usize> {
+    let is_punct = |token: &Token, values: &[&str]| {
+        token.kind == TokenType::Punctuation && values.contains(&token.value.as_str())
+    };
+    let significant: Vec<(usize, &Token)> = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| !t.kind.is_trivia())
+        .collect();
+    significant
+        .windows(2)
+        .filter(|pair| is_punct(pair[0].1, &[","]) && is_punct(pair[1].1, &[")", "]", "}"]))
+        .map(|pair| pair[0].0)
+        .collect()
+}
+
+// Source Buffer
+//
+// Text plus the byte offset every line starts at, computed once so offsets
+// and line/column positions can be converted repeatedly without rescanning.
+// Lines are numbered from 1 and columns count characters from 1, matching
+// tokens. Lines end at `\n`, `\r\n` or a lone `\r`, as in the lexer, and a
+// trailing line ending starts a final, empty line.
+#[derive(Debug, Clone)]
+pub struct SourceBuffer<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceBuffer<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let bytes = text.as_bytes();
+        let ends_line =
+            |i: usize| bytes[i] == b'\n' || (bytes[i] == b'\r' && bytes.get(i + 1) != Some(&b'\n'));
+        let line_starts = std::iter::once(0)
+            .chain((0..bytes.len()).filter(|&i| ends_line(i)).map(|i| i + 1))
+            .collect();
+        SourceBuffer { text, line_starts }
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    // Byte offset `line` starts at.
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        line.checked_sub(1)
+            .and_then(|i| self.line_starts.get(i))
+            .copied()
+    }
+
+    // Text of `line` without its line terminator.
+    pub fn line(&self, line: usize) -> Option<&'a str> {
+        let start = self.line_start(line)?;
+        let end = self.line_start(line + 1).unwrap_or(self.text.len());
+        let text = &self.text[start..end];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    // Line and column of the character at byte `offset`. Finding the line is
+    // a binary search; the column counts characters within that line. `None`
+    // if `offset` is past the end or inside a character.
+    pub fn offset_to_position(&self, offset: usize) -> Option<(usize, usize)> {
+        if !self.text.is_char_boundary(offset) {
+            return None;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let start = self.line_starts[line - 1];
+        Some((line, self.text[start..offset].chars().count() + 1))
+    }
+}
+
+// Rewrites token columns to where they appear once tabs in `source` are
+// expanded to `tab_width`-wide stops, for display in tab-expanding views.
+// `source` must be the text the tokens were lexed from.
+pub fn expand_tab_columns(tokens: &mut [Token], source: &str, tab_width: usize) {
+    let buffer = SourceBuffer::new(source);
+
+    for token in tokens {
+        let Some(line) = buffer.line(token.line).filter(|_| token.column > 0) else {
+            continue;
+        };
+        let width = line
+            .chars()
+            .take(token.column - 1)
+            .fold(0, |col, c| match c {
+                '\t' if tab_width > 0 => (col / tab_width + 1) * tab_width,
+                _ => col + 1,
+            });
+        token.column = width + 1;
+    }
+}
+
+// The token covering `column` on `line`, judged by where each token starts
+// and how many characters its source spelling has. Tokens spanning several
+// lines only cover the part on their first line.
+pub fn token_at_position(tokens: &[Token], line: usize, column: usize) -> Option<&Token> {
+    tokens.iter().find(|token| {
+        let width = token
+            .source_text()
+            .lines()
+            .next()
+            .map_or(0, |first| first.chars().count());
+        token.line == line && (token.column..token.column + width).contains(&column)
+    })
+}
+
+// Moves the span of every token starting at or after byte `from` by `delta`,
+// to follow an edit that grew or shrank the text before them. After
+// `replace_value`, pass the edited token's old end and its change in length.
+// Panics if a span would move before the start of the input.
+pub fn reflow_spans<T>(tokens: &mut [Token<T>], from: usize, delta: isize) {
+    let shift = |offset: usize| {
+        offset
+            .checked_add_signed(delta)
+            .expect("span shifted before start of input")
+    };
+    for token in tokens.iter_mut().filter(|t| t.span.start >= from) {
+        token.span = Span::new(shift(token.span.start), shift(token.span.end));
+    }
+}
+
+// Approximate bytes held by `tokens`: each token's inline size plus the heap
+// capacity of its `value` and `raw` strings. The slice's own spare capacity
+// and payload heap data are not counted.
+pub fn token_memory_usage<T>(tokens: &[Token<T>]) -> usize {
+    tokens
+        .iter()
+        .map(|token| {
+            std::mem::size_of::<Token<T>>()
+                + token.value.capacity()
+                + token.raw.as_ref().map_or(0, String::capacity)
+        })
+        .sum()
+}
+
+// Token Stream Fingerprints
+//
+// 64-bit FNV-1a over each token's kind, text, position and span. The hash is
+// spelled out here rather than taken from `std::hash`, whose output may
+// change between Rust releases, so fingerprints can be stored on disk.
+pub fn fingerprint_tokens(tokens: &[Token]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(PRIME);
+        }
+    };
+    for token in tokens {
+        feed(token.kind.name().as_bytes());
+        feed(&[0]);
+        feed(token.source_text().as_bytes());
+        feed(&[0]);
+        for n in [token.line, token.column, token.span.start, token.span.end] {
+            feed(&(n as u64).to_le_bytes());
+        }
+    }
+    hash
+}
+
+// True when `source` still lexes, with the default parser, to a stream
+// whose fingerprint is `cached_fingerprint`. Sources that no longer parse
+// never verify.
+pub fn verify_cache(source: &str, cached_fingerprint: u64) -> bool {
+    MinimalParser::new()
+        .parse(source)
+        .is_ok_and(|tokens| fingerprint_tokens(&tokens) == cached_fingerprint)
+}
+
+// Counts tokens per kind, keyed by kind name so iteration order is stable.
+pub fn token_counts_sorted(tokens: &[Token]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for token in tokens {
+        *counts.entry(token.kind.to_string()).or_insert(0) += 1;
+    }
+    counts
+}
+
+// Buckets tokens by `key`, keeping source order within each bucket.
+pub fn group_by<K: Eq + Hash, F: Fn(&Token) -> K>(
+    tokens: &[Token],
+    key: F,
+) -> HashMap<K, Vec<&Token>> {
+    let mut groups: HashMap<K, Vec<&Token>> = HashMap::new();
+    for token in tokens {
+        groups.entry(key(token)).or_default().push(token);
+    }
+    groups
+}
+
+// Line Ending Detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Mixed,
+    None,
+}
+
+pub fn detect_line_ending(input: &str) -> LineEnding {
+    let newlines = input.matches('\n').count();
+    let crlf = input.matches("\r\n").count();
+    let lf = newlines - crlf;
+
+    match (lf, crlf) {
+        (0, 0) => LineEnding::None,
+        (_, 0) => LineEnding::Lf,
+        (0, _) => LineEnding::Crlf,
+        _ => LineEnding::Mixed,
+    }
+}
+
+// Collects the contents of string literals and comments, in source order.
+pub fn extract_text(tokens: &[Token]) -> Vec<&str> {
+    tokens
+        .iter()
+        .filter(|t| {
+            matches!(
+                t.kind,
+                TokenType::StringLiteral | TokenType::Comment | TokenType::DocComment
+            )
+        })
+        .map(|t| t.value.as_str())
+        .collect()
+}
+
+// Format Specifiers
+
+// A printf-style conversion such as `%-5.2f`. Offsets are bytes into the
+// string token's decoded value, covering the whole specifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatSpec {
+    pub conversion: char,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Finds `%` conversions in a string token's value: optional flags, width,
+// precision and length modifier, then a conversion character. `%%` is an
+// escaped percent sign and anything that does not end in a known conversion
+// is skipped. Non-string tokens have no specifiers.
+pub fn extract_format_specifiers(token: &Token) -> Vec<FormatSpec> {
+    let mut specs = Vec::new();
+    if token.kind != TokenType::StringLiteral {
+        return specs;
+    }
+
+    let text = token.value.as_str();
+    let mut search = 0;
+    while let Some(found) = text[search..].find('%') {
+        let start = search + found;
+        let rest = &text[start + 1..];
+        let width = |c: char| c.is_ascii_digit() || c == '*';
+        let body = rest
+            .trim_start_matches(['-', '+', ' ', '#', '0'])
+            .trim_start_matches(width);
+        let body = match body.strip_prefix('.') {
+            Some(precision) => precision.trim_start_matches(width),
+            None => body,
+        };
+        let body = body.trim_start_matches(['h', 'l', 'L', 'q', 'j', 'z', 't']);
+        let end = start + 1 + rest.len() - body.len();
+
+        match body.chars().next() {
+            Some('%') if end == start + 1 => search = end + 1,
+            Some(c) if "diouxXeEfFgGaAcspn".contains(c) => {
+                specs.push(FormatSpec {
+                    conversion: c,
+                    start,
+                    end: end + 1,
+                });
+                search = end + 1;
+            }
+            _ => search = start + 1,
+        }
+    }
+
+    specs
+}
+
+// String Interpolation
+#[derive(Debug, Clone)]
+pub enum InterpolationPart {
+    Literal(String),
+    Expression(Vec<Token>),
+}
+
+// A string token's value cut at each `${...}`. Expression tokens are lexed
+// on their own, so their positions are relative to the expression text.
+#[derive(Debug, Clone)]
+pub struct StringInterpolation {
+    pub parts: Vec<InterpolationPart>,
+}
+
+impl StringInterpolation {
+    pub fn literals(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            InterpolationPart::Literal(text) => Some(text.as_str()),
+            InterpolationPart::Expression(_) => None,
+        })
+    }
+
+    pub fn expressions(&self) -> impl Iterator<Item = &[Token]> {
+        self.parts.iter().filter_map(|part| match part {
+            InterpolationPart::Literal(_) => None,
+            InterpolationPart::Expression(tokens) => Some(tokens.as_slice()),
+        })
+    }
+}
+
+impl MinimalParser {
+    // Splits a string token into literal text and `${...}` expressions, each
+    // expression lexed with this parser's config. Braces nest, so `${ {a} }`
+    // is one expression; empty literal parts are left out.
+    pub fn interpolate(&self, token: &Token) -> Result<StringInterpolation, ParserError> {
+        if token.kind != TokenType::StringLiteral {
+            return Err(ParserError::UnexpectedToken(token.value.clone()));
+        }
+
+        let text = token.value.as_str();
+        let mut parts = Vec::new();
+        let mut rest = text;
+        while let Some(open) = rest.find("${") {
+            if open > 0 {
+                parts.push(InterpolationPart::Literal(rest[..open].to_string()));
+            }
+            let body = &rest[open + 2..];
+            let mut depth = 0;
+            let close = body.char_indices().find_map(|(i, c)| match c {
+                '{' => {
+                    depth += 1;
+                    None
+                }
+                '}' if depth == 0 => Some(i),
+                '}' => {
+                    depth -= 1;
+                    None
+                }
+                _ => None,
+            });
+            let Some(close) = close else {
+                return Err(ParserError::SyntaxError {
+                    line: token.line,
+                    column: token.column,
+                    message: "unterminated interpolation: expected }".to_string(),
+                    snippet: String::new(),
+                });
+            };
+            parts.push(InterpolationPart::Expression(self.parse(&body[..close])?));
+            rest = &body[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(InterpolationPart::Literal(rest.to_string()));
+        }
+
+        Ok(StringInterpolation { parts })
+    }
+}
+
+// Parse Metrics
+//
+// Timing and size of a single parse, returned to the caller rather than
+// printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParseMetrics {
+    pub duration: Duration,
+    pub token_count: usize,
+    pub byte_count: usize,
+}
+
+// Scan Metrics
+//
+// How many times each branch of the scan loop produced a token. A word
+// counts as a number or identifier scan by how it was classified; words of
+// other kinds, comments and sigils are not counted. `bytes_examined` is the
+// length of all line text handed to the scan loop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanMetrics {
+    pub identifier_scans: usize,
+    pub number_scans: usize,
+    pub string_scans: usize,
+    pub operator_scans: usize,
+    pub bytes_examined: usize,
+}
+
+// Benchmark Results
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkResult {
+    pub duration: Duration,
+    pub token_count: usize,
+    pub byte_count: usize,
+}
+
+impl BenchmarkResult {
+    // Tokens produced per second of parsing; zero when no time was measured.
+    pub fn tokens_per_sec(&self) -> f64 {
+        let secs = self.duration.as_secs_f64();
+        if secs > 0.0 {
+            self.token_count as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+// Totals over a set of benchmark runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkSummary {
+    pub total_tokens: usize,
+    pub total_bytes: usize,
+    pub total_duration: Duration,
+    // Total bytes over total time, in megabytes (10^6 bytes) per second.
+    // Zero when no time was measured.
+    pub mb_per_sec: f64,
+}
+
+impl BenchmarkSummary {
+    pub fn from_results(results: &[BenchmarkResult]) -> BenchmarkSummary {
+        let total_tokens = results.iter().map(|r| r.token_count).sum();
+        let total_bytes = results.iter().map(|r| r.byte_count).sum();
+        let total_duration: Duration = results.iter().map(|r| r.duration).sum();
+        let secs = total_duration.as_secs_f64();
+        let mb_per_sec = if secs > 0.0 {
+            total_bytes as f64 / 1e6 / secs
+        } else {
+            0.0
+        };
+        BenchmarkSummary {
+            total_tokens,
+            total_bytes,
+            total_duration,
+            mb_per_sec,
+        }
+    }
+}
+
+// Performance Benchmarking
+//
+// Parses each input once and times it. Inputs that fail to parse have no
+// result. With `print` set, each result or error is also written out.
+pub fn run_benchmark(parser: &dyn Parser, inputs: &[&str], print: bool) -> Vec<BenchmarkResult> {
+    use std::time::Instant;
+
+    let mut results = Vec::new();
+    for input in inputs {
+        let start = Instant::now();
+        match parser.parse(input) {
+            Ok(tokens) => {
+                let result = BenchmarkResult {
+                    duration: start.elapsed(),
+                    token_count: tokens.len(),
+                    byte_count: input.len(),
+                };
+                if print {
+                    println!(
+                        "Parsed {} tokens in {:?} ({:.0} tokens/sec). Input length: {}",
+                        result.token_count,
+                        result.duration,
+                        result.tokens_per_sec(),
+                        result.byte_count
+                    );
+                }
+                results.push(result);
+            }
+            Err(e) if print => eprintln!("Parsing error: {}", e),
+            Err(_) => {}
+        }
+    }
+    results
+}
+
+// Unit Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_basic_parsing() {
+        let parser = MinimalParser::new();
+        let input = "hello world";
+
+        let result = parser.parse(input);
+        assert!(result.is_ok());
+
+        let tokens = result.unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].value, "hello");
+        assert_eq!(tokens[1].value, "world");
+    }
+
+    #[test]
+    fn test_token_classification() {
+        let parser = MinimalParser::new();
+
+        assert_eq!(parser.classify_token("+"), TokenType::Operator);
+        assert_eq!(parser.classify_token("if"), TokenType::Keyword);
+        assert_eq!(parser.classify_token("variable"), TokenType::Identifier);
+        assert_eq!(parser.classify_token("42"), TokenType::Literal);
+    }
+
+    #[test]
+    fn test_boolean_recognition_flag() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            recognize_booleans: true,
+            ..ParserConfig::default()
+        });
+        assert_eq!(parser.classify_token("true"), TokenType::Boolean);
+        assert_eq!(parser.classify_token("false"), TokenType::Boolean);
+
+        let parser = MinimalParser::new();
+        assert_eq!(parser.classify_token("true"), TokenType::Identifier);
+    }
+
+    #[test]
+    fn test_lexer_drain_tokens_resets_state() {
+        let config = ParserConfig::default();
+        let mut lexer = Lexer::new(&config);
+
+        lexer.lex("first input\nsecond line").unwrap();
+        let first = lexer.drain_tokens();
+        assert_eq!(first.len(), 4);
+        assert_eq!(first[3].line, 2);
+        assert!(lexer.tokens().is_empty());
+
+        lexer.lex("fresh").unwrap();
+        let second = lexer.drain_tokens();
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].value, "fresh");
+        assert_eq!(second[0].line, 1);
+
+        // A heredoc left open by one input does not swallow the next.
+        let config = ParserConfig {
+            heredocs: true,
+            ..ParserConfig::default()
+        };
+        let mut lexer = Lexer::new(&config);
+        lexer.lex_next_line("x = <<END", "\n").unwrap();
+        lexer.drain_tokens();
+        lexer.lex("fresh").unwrap();
+        assert_eq!(lexer.bytes_consumed(), 5);
+        let third = lexer.drain_tokens();
+        assert_eq!(third.len(), 1);
+        assert_eq!(third[0].span, Span::new(0, 5));
+    }
+
+    #[test]
+    fn test_integer_bounds_check() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            check_integer_bounds: true,
+            ..ParserConfig::default()
+        });
+        let huge = "1".repeat(50);
+
+        match parser.parse(&format!("x = {}", huge)) {
+            Err(ParserError::SyntaxError {
+                line,
+                column,
+                message,
+                ..
+            }) => {
+                assert_eq!((line, column), (1, 5));
+                assert!(message.contains("integer literal too large"));
+            }
+            other => panic!("expected bounds error, got {:?}", other),
+        }
+
+        assert!(parser.parse("x = 255").is_ok());
+        assert!(MinimalParser::new().parse(&huge).is_ok());
+    }
+
+    #[test]
+    fn test_token_is_at() {
+        let token = Token {
+            kind: TokenType::Identifier,
+            value: "x".to_string(),
+            line: 3,
+            column: 1,
+            raw: None,
+            span: Span::new(7, 8),
+            payload: (),
+        };
+
+        assert!(token.is_at(3, 1));
+        assert!(!token.is_at(3, 2));
+        assert!(!token.is_at(2, 1));
+    }
+
+    #[test]
+    fn test_group_brackets() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse("f ( a [ b ] ) c").unwrap();
+        let trees = group_brackets(&tokens, 16).unwrap();
+
+        assert_eq!(trees.len(), 3);
+        match &trees[1] {
+            TokenTree::Group {
+                open,
+                children,
+                close,
+            } => {
+                assert_eq!((open.value.as_str(), close.value.as_str()), ("(", ")"));
+                assert_eq!(children.len(), 2);
+                assert!(matches!(
+                    &children[1],
+                    TokenTree::Group { children, .. } if children.len() == 1
+                ));
+            }
+            other => panic!("expected group, got {:?}", other),
+        }
+
+        assert!(group_brackets(&parser.parse("( ]").unwrap(), 16).is_err());
+        assert!(group_brackets(&parser.parse("( a").unwrap(), 16).is_err());
+    }
+
+    #[test]
+    fn test_group_brackets_ignores_brackets_in_strings() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse("f(\"(\", \"]\") g").unwrap();
+        let trees = group_brackets(&tokens, 16).unwrap();
+
+        assert_eq!(trees.len(), 3);
+        match &trees[1] {
+            TokenTree::Group { children, .. } => {
+                let values: Vec<&str> = children
+                    .iter()
+                    .map(|child| match child {
+                        TokenTree::Leaf(token) => token.value.as_str(),
+                        TokenTree::Group { .. } => panic!("unexpected nested group"),
+                    })
+                    .collect();
+                assert_eq!(values, ["(", ",", "]"]);
+            }
+            TokenTree::Leaf(token) => panic!("expected group, got {:?}", token),
+        }
+    }
+
+    #[test]
+    fn test_group_brackets_depth_limit() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse(&"( ".repeat(100_000)).unwrap();
+
+        match group_brackets(&tokens, 1000) {
+            Err(ParserError::SyntaxError { message, .. }) => {
+                assert_eq!(message, "maximum nesting depth exceeded");
+            }
+            other => panic!("expected depth error, got {:?}", other.map(|t| t.len())),
+        }
+    }
+
+    #[test]
+    fn test_default_config_matches_minimal_parser() {
+        let config = ParserConfig::default();
+        let parser = MinimalParser::new();
+        let samples = [
+            "+", "-", "*", "/", "if", "else", "while", "variable", "42", "{", "true",
+        ];
+
+        for sample in samples {
+            assert_eq!(
+                config.classify(sample),
+                parser.classify_token(sample),
+                "{}",
+                sample
+            );
+        }
+        assert_eq!(config.classify("while"), TokenType::Keyword);
+        assert_eq!(config.classify("*"), TokenType::Operator);
+        assert_eq!(config.classify("#"), TokenType::Punctuation);
+    }
+
+    #[test]
+    fn test_format_tokens_normalizes_spacing() {
+        let parser = MinimalParser::new();
+        let input = "x   =  foo (  a ,   b )   ;\n  y =\t( a +   b )  * 2 ;";
+        let tokens = parser.parse(input).unwrap();
+
+        assert_eq!(format_tokens(&tokens), "x = foo(a, b); y = (a + b) * 2;");
+        assert_eq!(format_tokens(&[]), "");
+    }
+
+    #[test]
+    fn test_format_tokens_keeps_unary_operators_attached() {
+        let parser = MinimalParser::new();
+        let format = |input: &str| format_tokens(&parser.parse(input).unwrap());
+
+        assert_eq!(format("f( - 1 )"), "f(-1)");
+        assert_eq!(format("x =  - 1"), "x = -1");
+        assert_eq!(format("- a - b"), "-a - b");
+        assert_eq!(format("g(a, -b, !c)"), "g(a, -b, !c)");
+    }
+
+    #[test]
+    fn test_format_tokens_keeps_strings_and_comments() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            line_comment: Some("//".to_string()),
+            ..ParserConfig::default()
+        });
+        let format = |input: &str| format_tokens(&parser.parse(input).unwrap());
+
+        assert_eq!(format("say ( \"hi there\" , x )"), "say(\"hi there\", x)");
+        assert_eq!(format("f( \"(\" ,\",\" )"), "f(\"(\", \",\")");
+        assert_eq!(format("x = 1 // note"), "x = 1 // note");
+        assert_eq!(format("x = 1 // note\ny = 2"), "x = 1 // note\ny = 2");
+    }
+
+    #[test]
+    fn test_delimited_mode_escapes_separator() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            delimited: Some(DelimitedMode {
+                separator: ',',
+                escape: '\\',
+            }),
+            ..ParserConfig::default()
+        });
+
+        let tokens = parser.parse("a\\,b,c\nx,,y z").unwrap();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["a,b", "c", "x", "", "y z"]);
+        assert!(tokens.iter().all(|t| t.kind == TokenType::Literal));
+        assert_eq!((tokens[2].line, tokens[2].column), (2, 1));
+        assert_eq!(tokens[1].raw.as_deref(), None);
+        assert_eq!(tokens[0].raw.as_deref(), Some("a\\,b"));
+        assert_eq!((tokens[4].column, tokens[4].value.as_str()), (4, "y z"));
+    }
+
+    #[test]
+    fn test_minimal_parser_supported_features() {
+        let basic = FeatureSet::STRINGS | FeatureSet::FLOATS | FeatureSet::SPANS;
+
+        assert_eq!(
+            MinimalParser::new().supported_features(),
+            basic | FeatureSet::COMMENTS | FeatureSet::RECOVERY
+        );
+        assert_eq!(
+            JsonLexer::new().supported_features(),
+            basic | FeatureSet::RECOVERY
+        );
+    }
+
+    #[test]
+    fn test_string_token_keeps_raw_spelling() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse(r#"say "a\nb" now"#).unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[1].kind, TokenType::StringLiteral);
+        assert_eq!(tokens[1].value, "a\nb");
+        assert_eq!(tokens[1].raw.as_deref(), Some(r#""a\nb""#));
+        assert_eq!(tokens[0].raw, None);
+        assert_eq!(tokens[2].column, 12);
+    }
+
+    #[test]
+    fn test_max_identifier_length() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            max_identifier_length: Some(5),
+            ..ParserConfig::default()
+        });
+
+        assert!(parser.parse("abc").is_ok());
+        assert!(parser.parse("abcde + 1").is_ok());
+        match parser.parse("abc abcdef") {
+            Err(ParserError::SyntaxError {
+                line,
+                column,
+                message,
+                ..
+            }) => {
+                assert_eq!((line, column), (1, 5));
+                assert!(message.starts_with("identifier too long"));
+            }
+            other => panic!("expected length error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_classification_cache_agrees_with_uncached() {
+        let input = "if x + 5 while y\nx = x * 42 if ( true )\n".repeat(20);
+        let uncached = MinimalParser::new().parse(&input).unwrap();
+        let cached = MinimalParser::with_config(ParserConfig {
+            classification_cache_size: Some(3),
+            ..ParserConfig::default()
+        })
+        .parse(&input)
+        .unwrap();
+
+        let kinds = |tokens: &[Token]| -> Vec<(TokenType, String)> {
+            tokens
+                .iter()
+                .map(|t| (t.kind.clone(), t.value.clone()))
+                .collect()
+        };
+        assert_eq!(kinds(&cached), kinds(&uncached));
+    }
+
+    #[test]
+    fn test_classification_cache_invalidated_on_config_change() {
+        let plain = ParserConfig {
+            classification_cache_size: Some(8),
+            ..ParserConfig::default()
+        };
+        let booleans = ParserConfig {
+            recognize_booleans: true,
+            ..plain.clone()
+        };
+
+        let mut lexer = Lexer::new(&plain);
+        lexer.lex("true").unwrap();
+        assert_eq!(lexer.drain_tokens()[0].kind, TokenType::Identifier);
+
+        lexer.set_config(&booleans);
+        lexer.lex("true").unwrap();
+        assert_eq!(lexer.drain_tokens()[0].kind, TokenType::Boolean);
+    }
+
+    #[test]
+    fn test_extract_text_collects_strings_and_comments() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            line_comment: Some("//".to_string()),
+            ..ParserConfig::default()
+        });
+        let input = "greet(\"hello world\")// say hi\nlog \"done\" 42";
+        let tokens = parser.parse(input).unwrap();
+
+        assert_eq!(extract_text(&tokens), ["hello world", "say hi", "done"]);
+        assert_eq!(tokens[4].kind, TokenType::Comment);
+        assert_eq!(tokens[4].raw.as_deref(), Some("// say hi"));
+    }
+
+    #[test]
+    fn test_tokenize_reader_lines() {
+        let parser = MinimalParser::new();
+        let reader = std::io::Cursor::new("let x\n\nx + 1\n");
+        let lines: Vec<Vec<Token>> = parser
+            .tokenize_reader_lines(reader)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), 2);
+        assert!(lines[1].is_empty());
+        assert_eq!(lines[2].len(), 3);
+        assert!(lines[2].iter().all(|t| t.line == 3));
+    }
+
+    #[test]
+    fn test_sigils_attach_to_identifiers() {
+        let config = ParserConfig {
+            sigils: ['@', '$'].into_iter().collect(),
+            attach_sigils: true,
+            ..ParserConfig::default()
+        };
+        let tokens = MinimalParser::with_config(config.clone())
+            .parse("@route $ x @my_route $x1 @_ @1")
+            .unwrap();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(
+            values,
+            ["@route", "$", "x", "@my_route", "$x1", "@_", "@", "1"]
+        );
+        assert_eq!(tokens[0].kind, TokenType::Sigil);
+        assert_eq!(tokens[1].kind, TokenType::Sigil);
+        assert!(tokens[3..7].iter().all(|t| t.kind == TokenType::Sigil));
+        assert_eq!(tokens[7].kind, TokenType::Literal);
+
+        let detached = ParserConfig {
+            attach_sigils: false,
+            ..config
+        };
+        let tokens = MinimalParser::with_config(detached)
+            .parse("@route")
+            .unwrap();
+        assert_eq!(tokens[0].value, "@");
+        assert_eq!(tokens[0].kind, TokenType::Sigil);
+        assert_eq!(tokens[1].value, "route");
+        assert_eq!(tokens[1].kind, TokenType::Identifier);
+    }
+
+    #[test]
+    fn test_operator_arity() {
+        use OperatorContext::{Infix, Prefix};
+
+        assert_eq!(operator_arity("-", Prefix), Arity::Unary);
+        assert_eq!(operator_arity("-", Infix), Arity::Binary);
+        assert_eq!(operator_arity("!", Prefix), Arity::Unary);
+        assert_eq!(operator_arity("!", Infix), Arity::Unary);
+        assert_eq!(operator_arity("+", Prefix), Arity::Unary);
+        assert_eq!(operator_arity("+", Infix), Arity::Binary);
+        assert_eq!(operator_arity("*", Infix), Arity::Binary);
+    }
+
+    #[test]
+    fn test_token_counts_sorted() {
+        let tokens = MinimalParser::new().parse("if x + 5 + y").unwrap();
+        let counts: Vec<(String, usize)> = token_counts_sorted(&tokens).into_iter().collect();
+
+        assert_eq!(
+            counts,
+            [
+                ("Identifier".to_string(), 2),
+                ("Keyword".to_string(), 1),
+                ("Literal".to_string(), 1),
+                ("Operator".to_string(), 2),
+            ]
+        );
+    }
+
+    #[cfg(feature = "bumpalo")]
+    #[test]
+    fn test_parse_in_bump_arena() {
+        let bump = bumpalo::Bump::new();
+        let parser = MinimalParser::new();
+        let input = "if x + 5\nwhile \"y z\"";
+
+        let arena_tokens = parser.parse_in(input, &bump).unwrap();
+        let heap_tokens = parser.parse(input).unwrap();
+
+        assert_eq!(arena_tokens.len(), heap_tokens.len());
+        for (arena, heap) in arena_tokens.iter().zip(&heap_tokens) {
+            assert_eq!(arena.kind, heap.kind);
+            assert_eq!(arena.value, heap.value);
+            assert_eq!((arena.line, arena.column), (heap.line, heap.column));
+        }
+        assert!(bump.allocated_bytes() > 0);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_bincode_round_trip() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse("if x + 5\nwhile \"y z\" 3.5").unwrap();
+
+        let bytes = to_bincode(&tokens);
+        let decoded = from_bincode(&bytes).unwrap();
+
+        assert_eq!(decoded.len(), tokens.len());
+        for (before, after) in tokens.iter().zip(&decoded) {
+            assert_eq!((&before.kind, &before.value), (&after.kind, &after.value));
+            assert_eq!((before.line, before.column), (after.line, after.column));
+            assert_eq!((&before.raw, before.span), (&after.raw, after.span));
+        }
+        assert!(bytes.len() < serde_json::to_vec(&tokens).unwrap().len());
+        assert!(from_bincode(&bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json_round_trip() {
+        let tokens = MinimalParser::new().parse("if x == \"y z\" 3.5").unwrap();
+
+        let json = serde_json::to_string(&tokens).unwrap();
+        assert!(json.contains("\"kind\":\"Keyword\""));
+        assert!(json.contains("\"kind\":\"StringLiteral\""));
+        let decoded: Vec<Token> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, tokens);
+
+        let error = MinimalParser::new().parse("\"open").unwrap_err();
+        let json = serde_json::to_string(&error).unwrap();
+        let decoded: ParserError = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.to_string(), error.to_string());
+    }
+
+    #[test]
+    fn test_detect_line_ending() {
+        assert_eq!(detect_line_ending("a\nb\n"), LineEnding::Lf);
+        assert_eq!(detect_line_ending("a\r\nb\r\n"), LineEnding::Crlf);
+        assert_eq!(detect_line_ending("a\r\nb\nc"), LineEnding::Mixed);
+        assert_eq!(detect_line_ending("single line"), LineEnding::None);
+    }
+
+    #[test]
+    fn test_scan_raw_region() {
+        let config = ParserConfig::default();
+        let mut lexer = Lexer::new(&config);
+        let input = "a <% if x { y } %> b\n<%first\nsecond%>c";
+        lexer
+            .scan_raw_region(input, "<%", "%>", TokenType::Literal)
+            .unwrap();
+        let tokens = lexer.drain_tokens();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+
+        assert_eq!(values, ["a", " if x { y } ", "b", "first\nsecond", "c"]);
+        assert_eq!(tokens[1].kind, TokenType::Literal);
+        assert_eq!(tokens[1].raw.as_deref(), Some("<% if x { y } %>"));
+        assert_eq!((tokens[1].line, tokens[1].column), (1, 3));
+        assert_eq!((tokens[2].line, tokens[2].column), (1, 20));
+        assert_eq!((tokens[3].line, tokens[3].column), (2, 1));
+        assert_eq!((tokens[4].line, tokens[4].column), (3, 9));
+
+        assert!(lexer
+            .scan_raw_region("<% open", "<%", "%>", TokenType::Literal)
+            .is_err());
+    }
+
+    #[test]
+    fn test_check_adjacent_identifiers() {
+        let parser = MinimalParser::new();
+
+        let warnings = check_adjacent_identifiers(&parser.parse("foo bar").unwrap());
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(
+            &warnings[0],
+            ParserError::SyntaxError {
+                line: 1,
+                column: 5,
+                ..
+            }
+        ));
+
+        assert!(check_adjacent_identifiers(&parser.parse("foo + bar").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_utf8_lossy() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse_utf8_lossy(b"ok \xFF then").unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[1].value, "\u{FFFD}");
+        assert_eq!(tokens[2].value, "then");
+    }
+
+    #[test]
+    fn test_semantic_category() {
+        let tokens = MinimalParser::new().parse("while 42 \"text\"").unwrap();
+        let categories: Vec<&str> = tokens.iter().map(Token::semantic_category).collect();
+
+        assert_eq!(
+            categories,
+            ["keyword.control", "constant.numeric", "string.quoted"]
+        );
+    }
+
+    #[test]
+    fn test_parse_with_progress_reports_bytes_consumed() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            max_identifier_length: Some(4),
+            ..ParserConfig::default()
+        });
+
+        let input = "x = 1\ny = toolong";
+        let (result, consumed) = parser.parse_with_progress(input);
+        assert!(result.is_err());
+        assert_eq!(consumed, input.find("toolong").unwrap());
+
+        let (result, consumed) = parser.parse_with_progress("a + b\r\n");
+        assert_eq!(result.unwrap().len(), 3);
+        assert_eq!(consumed, 7);
+    }
+
+    #[test]
+    fn test_is_trivial() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            line_comment: Some("#".to_string()),
+            ..ParserConfig::default()
+        });
+
+        assert!(parser.is_trivial(""));
+        assert!(parser.is_trivial("  \n\t\n"));
+        assert!(parser.is_trivial("# just a note\n   # another\n\n"));
+        assert!(!parser.is_trivial("# header\nx = 1\n"));
+        assert!(!MinimalParser::new().is_trivial("# no comments configured"));
+    }
+
+    #[test]
+    fn test_is_trivial_follows_lexer_trivia() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            doc_block_comment: Some(("/**".to_string(), "*/".to_string())),
+            skip_unrecognized: true,
+            ..ParserConfig::default()
+        });
+
+        assert!(parser.is_trivial("/** Module notes\n * spanning lines\n */\n"));
+        assert!(parser.is_trivial("\u{0}\u{fffd}\n  \u{7f}"));
+        assert!(!parser.is_trivial("/** doc */ x"));
+        assert!(!parser.is_trivial("/** never closed"));
+    }
+
+    #[test]
+    fn test_operator_scanning_is_longest_match() {
+        let mut config = ParserConfig::default();
+        config
+            .operators
+            .extend(["+=", "->", "<<=", "<<"].map(String::from));
+        assert_eq!(config.max_operator_len(), 3);
+
+        let tokens = MinimalParser::with_config(config)
+            .parse("a+=b->c<<=1<<2-x")
+            .unwrap();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(
+            values,
+            ["a", "+=", "b", "->", "c", "<<=", "1", "<<", "2", "-", "x"]
+        );
+        assert_eq!(tokens[5].kind, TokenType::Operator);
+    }
+
+    #[test]
+    fn test_operator_scanning_scales_linearly() {
+        use std::time::{Duration, Instant};
+
+        let mut config = ParserConfig::default();
+        config
+            .operators
+            .extend(["+=", "->", "<<=", "<<", ">>=", ">>"].map(String::from));
+        let parser = MinimalParser::with_config(config);
+        let unit = "a<<=b->c+=-d>>e<<<f ";
+
+        let time = |repeats: usize| -> Duration {
+            let input = unit.repeat(repeats);
+            (0..3)
+                .map(|_| {
+                    let start = Instant::now();
+                    parser.parse(&input).unwrap();
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+
+        let small = time(500);
+        let large = time(8_000);
+        // 16x the input; quadratic scanning would be ~256x slower.
+        assert!(
+            large < small * 64,
+            "operator scanning not linear: {:?} vs {:?}",
+            small,
+            large
+        );
+    }
+
+    #[test]
+    fn test_token_map_payload() {
+        let tokens = MinimalParser::new().parse("let x = 5").unwrap();
+        let tagged: Vec<Token<u32>> = tokens
+            .into_iter()
+            .enumerate()
+            .map(|(i, token)| token.map_payload(|()| 100 + i as u32))
+            .collect();
+
+        let ids: Vec<u32> = tagged.iter().map(|t| t.payload).collect();
+        assert_eq!(ids, [100, 101, 102, 103]);
+        assert_eq!(tagged[3].value, "5");
+        assert_eq!(tagged[3].kind, TokenType::Literal);
+    }
+
+    #[test]
+    fn test_custom_radix_prefixes() {
+        let mut config = ParserConfig::default();
+        config.radix_prefixes.insert("$".to_string(), 16);
+        config.radix_prefixes.insert("%".to_string(), 2);
+
+        let tokens = MinimalParser::with_config(config.clone())
+            .parse("$FF %1010 %12 $")
+            .unwrap();
+        let kinds: Vec<TokenType> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenType::Literal,
+                TokenType::Literal,
+                TokenType::Punctuation,
+                TokenType::Punctuation
+            ]
+        );
+        assert_eq!(config.radix_literal("$FF"), Some((16, "FF")));
+        assert_eq!(config.radix_literal("%1010"), Some((2, "1010")));
+
+        config.check_integer_bounds = true;
+        let too_big = format!("${}", "F".repeat(33));
+        assert!(MinimalParser::with_config(config).parse(&too_big).is_err());
+    }
+
+    #[test]
+    fn test_json_lexer_token_kinds() {
+        use TokenType::*;
+
+        let tokens = JsonLexer::new().parse(r#"{"a":[1,true]}"#).unwrap();
+        let kinds: Vec<TokenType> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                Punctuation,
+                StringLiteral,
+                Punctuation,
+                Punctuation,
+                Literal,
+                Punctuation,
+                Boolean,
+                Punctuation,
+                Punctuation,
+            ]
+        );
+        assert_eq!(tokens[1].value, "a");
+
+        let tokens = JsonLexer::new().parse(r#"[-0.5e+3, null, "x y"]"#).unwrap();
+        assert_eq!(tokens[1].value, "-0.5e+3");
+        assert_eq!(tokens[3].kind, Keyword);
+        assert_eq!(tokens[5].value, "x y");
+
+        assert!(JsonLexer::new().parse("[01]").is_err());
+        assert!(JsonLexer::new().parse("{nope}").is_err());
+    }
+
+    #[test]
+    fn test_json_lexer_string_escapes() {
+        let value = |input: &str| JsonLexer::new().parse(input).unwrap()[1].value.clone();
+
+        assert_eq!(value(r#"["A\/\b"]"#), "A/\u{8}");
+        assert_eq!(value(r#"["\"\\\/"]"#), "\"\\/");
+        assert_eq!(value(r#"["\b\f\n\r\t"]"#), "\u{8}\u{c}\n\r\t");
+        assert_eq!(value(r#"["\u0041\u00e9\u20AC"]"#), "A\u{e9}\u{20ac}");
+        assert_eq!(value(r#"["\ud83d\ude00"]"#), "\u{1f600}");
+        assert_eq!(
+            JsonLexer::new().parse(r#"["a\/b"]"#).unwrap()[1]
+                .raw
+                .as_deref(),
+            Some(r#""a\/b""#)
+        );
+
+        for invalid in [
+            r#"["ab\0"]"#,
+            r#"["ab\x41"]"#,
+            r#"["ab\u12"]"#,
+            r#"["ab\ud83d"]"#,
+            r#"["ab\ude00"]"#,
+        ] {
+            match JsonLexer::new().parse(invalid) {
+                Err(ParserError::SyntaxError {
+                    column, message, ..
+                }) => {
+                    assert_eq!(column, 5, "{}", invalid);
+                    assert_eq!(message, "invalid escape in string literal");
+                }
+                other => panic!("expected invalid escape for {}, got {:?}", invalid, other),
+            }
+        }
+        assert!(JsonLexer::new().parse(r#"["ab\"#).is_err());
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        let tokens = MinimalParser::new().parse("  if x+ 10\n\"é\" é").unwrap();
+        let positions: Vec<(usize, usize)> = tokens.iter().map(|t| (t.line, t.column)).collect();
+
+        assert_eq!(positions, [(1, 3), (1, 6), (1, 7), (1, 9), (2, 1), (2, 5)]);
+    }
+
+    #[test]
+    fn test_expand_tab_columns() {
+        let source = "\tfoo\nx\t\tbar";
+        let mut tokens = MinimalParser::new().parse(source).unwrap();
+        assert_eq!(tokens[0].column, 2);
+
+        expand_tab_columns(&mut tokens, source, 4);
+        let columns: Vec<usize> = tokens.iter().map(|t| t.column).collect();
+        assert_eq!(columns, [5, 1, 9]);
+    }
+
+    #[test]
+    fn test_parse_owned_outlives_input_binding() {
+        let parser = MinimalParser::new();
+        let stream = {
+            let input = String::from("let x = \"hi there\"\nx + 1");
+            parser.parse_owned(input).unwrap()
+        };
+
+        let texts: Vec<&str> = stream.iter().map(|(_, text)| text).collect();
+        assert_eq!(texts, ["let", "x", "=", "\"hi there\"", "x", "+", "1"]);
+        assert_eq!(stream.tokens()[3].kind, TokenType::StringLiteral);
+        assert_eq!(stream.tokens()[4].line, 2);
+        assert_eq!(stream.len(), 7);
+    }
+
+    #[test]
+    fn test_edge_dot_floats() {
+        let strict = MinimalParser::with_config(ParserConfig {
+            allow_edge_dot_floats: false,
+            float_tokens: true,
+            ..ParserConfig::default()
+        });
+        assert_eq!(strict.classify_token("3.14"), TokenType::Float);
+        assert_eq!(strict.classify_token(".5"), TokenType::Punctuation);
+        assert_eq!(strict.classify_token("5."), TokenType::Punctuation);
+        assert_eq!(strict.classify_token("."), TokenType::Punctuation);
+
+        let lenient = MinimalParser::with_config(ParserConfig {
+            allow_edge_dot_floats: true,
+            float_tokens: true,
+            ..ParserConfig::default()
+        });
+        assert_eq!(lenient.classify_token(".5"), TokenType::Float);
+        assert_eq!(lenient.classify_token("5."), TokenType::Float);
+        assert_eq!(lenient.classify_token("."), TokenType::Punctuation);
+        assert_eq!(lenient.classify_token("1.2.3"), TokenType::Punctuation);
+    }
+
+    #[test]
+    fn test_merge_adjacent_tokens() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse("foo bar").unwrap();
+        let mut head = tokens[0].clone();
+        head.value = "fo".to_string();
+        head.span = Span::new(0, 2);
+        let mut tail = tokens[0].clone();
+        tail.value = "o".to_string();
+        tail.column = 3;
+        tail.span = Span::new(2, 3);
+
+        let merged = head.merge_with(&tail).unwrap();
+        assert_eq!(merged.value, "foo");
+        assert_eq!(merged.span, Span::new(0, 3));
+        assert_eq!(merged.column, 1);
+
+        // "foo" and "bar" are separated by a space.
+        assert!(tokens[0].merge_with(&tokens[1]).is_none());
+    }
+
+    #[test]
+    fn test_language_presets() {
+        let python = MinimalParser::with_config(ParserConfig::for_language(Language::Python));
+        let tokens = python.parse("def f(x): # doc\nreturn 'a'").unwrap();
+        assert_eq!(tokens[0].kind, TokenType::Keyword);
+        let comment = tokens
+            .iter()
+            .find(|t| t.kind == TokenType::Comment)
+            .unwrap();
+        assert_eq!(comment.value, "doc");
+        let string = tokens.last().unwrap();
+        assert_eq!(
+            (&string.kind, string.value.as_str()),
+            (&TokenType::StringLiteral, "a")
+        );
+
+        let sql = MinimalParser::with_config(ParserConfig::for_language(Language::Sql));
+        let tokens = sql.parse("select * from t -- all rows").unwrap();
+        assert_eq!(tokens[0].kind, TokenType::Keyword);
+        assert_eq!(tokens[1].kind, TokenType::Operator);
+        assert_eq!(tokens[4].kind, TokenType::Comment);
+
+        let c = MinimalParser::with_config(ParserConfig::for_language(Language::C));
+        let tokens = c.parse("x = 0xFF // mask").unwrap();
+        assert_eq!(tokens[2].kind, TokenType::Literal);
+        assert_eq!(tokens[3].kind, TokenType::Comment);
+
+        let json = ParserConfig::for_language(Language::Json);
+        assert_eq!(json.classify("null"), TokenType::Keyword);
+        assert_eq!(json.classify("true"), TokenType::Boolean);
+    }
+
+    #[test]
+    fn test_contains_kind_short_circuits() {
+        let parser = MinimalParser::new();
+        assert!(parser
+            .contains_kind("x = \"hi\"", TokenType::StringLiteral)
+            .unwrap());
+        assert!(!parser
+            .contains_kind("x + 1\ny", TokenType::StringLiteral)
+            .unwrap());
+
+        // The unterminated string on line 2 is never reached.
+        let input = "\"found\"\n\"broken";
+        assert!(parser.parse(input).is_err());
+        assert!(parser
+            .contains_kind(input, TokenType::StringLiteral)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_punctuation_runs_split_per_character() {
+        let tokens = MinimalParser::new().parse(");").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!((tokens[0].value.as_str(), tokens[0].column), (")", 1));
+        assert_eq!((tokens[1].value.as_str(), tokens[1].column), (";", 2));
+        assert_eq!(tokens[1].span, Span::new(1, 2));
+        assert!(tokens.iter().all(|t| t.kind == TokenType::Punctuation));
+
+        let grouped = MinimalParser::with_config(ParserConfig {
+            group_punctuation: true,
+            ..ParserConfig::default()
+        });
+        let tokens = grouped.parse(");").unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].value, ");");
+    }
+
+    #[test]
+    fn test_analyze_collects_tokens_diagnostics_and_metrics() {
+        let result = MinimalParser::new().analyze("x + 1\n\n\"open\ny");
+
+        let values: Vec<&str> = result.tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["x", "+", "1", "y"]);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert!(matches!(
+            result.diagnostics[0],
+            ParserError::SyntaxError { line: 3, .. }
+        ));
+        assert_eq!(
+            result.metrics,
+            SourceMetrics {
+                bytes: 14,
+                lines: 4,
+                blank_lines: 1,
+                tokens: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_heredoc_body_is_one_string_token() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            heredocs: true,
+            ..ParserConfig::default()
+        });
+        let input = "x <<END y\nfirst line\n  second\nEND\nz";
+        let tokens = parser.parse(input).unwrap();
+
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["x", "first line\n  second", "y", "z"]);
+        let doc = &tokens[1];
+        assert_eq!(doc.kind, TokenType::StringLiteral);
+        assert_eq!((doc.line, doc.column), (1, 3));
+        assert_eq!(
+            &input[doc.span.start..doc.span.end],
+            "<<END y\nfirst line\n  second\nEND"
+        );
+        assert_eq!(tokens[3].line, 5);
+
+        let err = parser.parse("cat <<EOF\nnever closed").unwrap_err();
+        assert!(matches!(
+            err,
+            ParserError::SyntaxError {
+                line: 1,
+                column: 5,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_token_table_macro() {
+        let from_macro = token_table!(keywords: ["if", "else"], operators: ["+", "=="]);
+        let by_hand = ParserConfig {
+            keywords: ["if", "else"].iter().map(|k| k.to_string()).collect(),
+            operators: ["+", "=="].iter().map(|o| o.to_string()).collect(),
+            ..ParserConfig::default()
+        };
+
+        for word in ["if", "else", "while", "+", "==", "-", "x", "42"] {
+            assert_eq!(
+                from_macro.classify(word),
+                by_hand.classify(word),
+                "{}",
+                word
+            );
+        }
+        assert_eq!(from_macro.classify("while"), TokenType::Identifier);
+
+        let empty = token_table!(keywords: [], operators: []);
+        assert!(empty.keywords.is_empty() && empty.operators.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_position_tracking_keeps_values_and_kinds() {
+        let input = "if x + 1 \"two words\"\nwhile y";
+        let tracked = MinimalParser::new().parse(input).unwrap();
+        let untracked = MinimalParser::with_config(ParserConfig {
+            track_positions: false,
+            ..ParserConfig::default()
+        })
+        .parse(input)
+        .unwrap();
+
+        assert_eq!(tracked.len(), untracked.len());
+        for (a, b) in tracked.iter().zip(&untracked) {
+            assert_eq!((&a.kind, &a.value, a.span), (&b.kind, &b.value, b.span));
+            assert_eq!((b.line, b.column), (0, 0));
+        }
+    }
+
+    #[test]
+    fn test_benchmark_summary_totals() {
+        let result = |millis, token_count, byte_count| BenchmarkResult {
+            duration: Duration::from_millis(millis),
+            token_count,
+            byte_count,
+        };
+        let results = [
+            result(100, 10, 200_000),
+            result(300, 20, 300_000),
+            result(600, 30, 500_000),
+        ];
+
+        let summary = BenchmarkSummary::from_results(&results);
+        assert_eq!(summary.total_tokens, 60);
+        assert_eq!(summary.total_bytes, 1_000_000);
+        assert_eq!(summary.total_duration, Duration::from_secs(1));
+        assert!((summary.mb_per_sec - 1.0).abs() < 1e-9);
+
+        assert_eq!(BenchmarkSummary::from_results(&[]).mb_per_sec, 0.0);
+    }
+
+    #[test]
+    fn test_find_trailing_commas() {
+        let lexer = JsonLexer::new();
+        let tokens = lexer.parse("[1, 2,]").unwrap();
+        assert_eq!(find_trailing_commas(&tokens), [4]);
+        assert_eq!(tokens[4].column, 6);
+
+        let tokens = lexer.parse("[1, 2]").unwrap();
+        assert!(find_trailing_commas(&tokens).is_empty());
+        let tokens = lexer.parse("[\",\" ]").unwrap();
+        assert!(find_trailing_commas(&tokens).is_empty());
+    }
+
+    #[test]
+    fn test_extract_format_specifiers() {
+        let tokens = MinimalParser::new().parse("\"value: %d and %s\"").unwrap();
+        let specs = extract_format_specifiers(&tokens[0]);
+        assert_eq!(
+            specs,
+            [
+                FormatSpec {
+                    conversion: 'd',
+                    start: 7,
+                    end: 9
+                },
+                FormatSpec {
+                    conversion: 's',
+                    start: 14,
+                    end: 16
+                },
+            ]
+        );
+
+        let tokens = MinimalParser::new().parse("\"100%% %-5.2lf %q\"").unwrap();
+        let specs = extract_format_specifiers(&tokens[0]);
+        assert_eq!(
+            specs,
+            [FormatSpec {
+                conversion: 'f',
+                start: 6,
+                end: 13
+            }]
+        );
+    }
+
+    #[test]
+    fn test_underscore_insensitive_keywords() {
+        let mut config = ParserConfig::default();
+        config.keywords.insert("goto".to_string());
+        assert_eq!(config.classify("go_to"), TokenType::Identifier);
+
+        config.underscore_insensitive_keywords = true;
+        assert_eq!(config.classify("go_to"), TokenType::Keyword);
+        assert_eq!(config.classify("_goto_"), TokenType::Keyword);
+        assert_eq!(config.classify("goto"), TokenType::Keyword);
+        assert_eq!(config.classify("go_on"), TokenType::Identifier);
+    }
+
+    #[test]
+    fn test_source_buffer_conversions() {
+        let text = "let a = 1;\r\n\tb\n\nnaïve = \"x\"\n";
+        let buffer = SourceBuffer::new(text);
+
+        let naive = |offset: usize| {
+            let before = &text[..offset];
+            let line = before.matches('\n').count() + 1;
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (line, text[line_start..offset].chars().count() + 1)
+        };
+        for (offset, _) in text.char_indices().chain([(text.len(), ' ')]) {
+            assert_eq!(
+                buffer.offset_to_position(offset),
+                Some(naive(offset)),
+                "{}",
+                offset
+            );
+        }
+
+        assert_eq!(buffer.line_count(), 5);
+        assert_eq!(buffer.line_start(2), Some(12));
+        assert_eq!(buffer.line(1), Some("let a = 1;"));
+        assert_eq!(buffer.line(3), Some(""));
+        assert_eq!(buffer.line(6), None);
+        assert_eq!(buffer.offset_to_position(text.len() + 1), None);
+        // Inside the two-byte `ï`.
+        assert_eq!(buffer.offset_to_position(text.find('ï').unwrap() + 1), None);
+    }
+
+    #[test]
+    fn test_parse_first_n_stops_early() {
+        let parser = MinimalParser::new();
+        // The unterminated string at the end would fail a full parse.
+        let input = format!("{}\"never closed", "a b c\n".repeat(10_000));
+        assert!(parser.parse(&input).is_err());
+
+        let tokens = parser.parse_first_n(&input, 4).unwrap();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["a", "b", "c", "a"]);
+        assert_eq!(tokens[3].line, 2);
+
+        assert_eq!(parser.parse_first_n("x y", 10).unwrap().len(), 2);
+        assert!(parser.parse_first_n("", 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_validate_transitions() {
+        let mut table = TransitionTable::permissive();
+        table.forbid(TokenType::Literal, TokenType::Identifier);
+        let parser = MinimalParser::new();
+
+        let errors = validate_transitions(&parser.parse("5 x").unwrap(), &table);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0],
+            ParserError::SyntaxError {
+                line: 1,
+                column: 3,
+                ..
+            }
+        ));
+        assert!(validate_transitions(&parser.parse("x 5 + y").unwrap(), &table).is_empty());
+
+        let mut strict = TransitionTable::new();
+        strict.allow(TokenType::Identifier, TokenType::Operator);
+        let tokens = parser.parse("x + y").unwrap();
+        assert_eq!(validate_transitions(&tokens, &strict).len(), 1);
+    }
+
+    #[test]
+    fn test_recovery_log() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            max_identifier_length: Some(5),
+            ..ParserConfig::default()
+        });
+        let (tokens, log) = parser
+            .parse_recovering("a \"open\nb\nlengthy c\nd")
+            .unwrap();
+
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["a", "open", "b", "d"]);
+        assert_eq!(log.len(), 2);
+        let shown: Vec<String> = log.entries().iter().map(|(_, a)| a.to_string()).collect();
+        assert_eq!(shown, ["skip", "synchronize to line 3"]);
+        assert!(matches!(
+            log.entries()[1].0,
+            ParserError::SyntaxError { line: 3, .. }
+        ));
+
+        let replacement = Token {
+            value: "x".to_string(),
+            ..tokens[0].clone()
+        };
+        assert_eq!(
+            RecoveryAction::Replace(replacement).to_string(),
+            "replace with x"
+        );
+        assert_eq!(RecoveryAction::Skip.to_string(), "skip");
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse("\"a ${x + 1} b\"").unwrap();
+        let interpolation = parser.interpolate(&tokens[0]).unwrap();
+
+        assert_eq!(interpolation.literals().collect::<Vec<_>>(), ["a ", " b"]);
+        let expressions: Vec<&[Token]> = interpolation.expressions().collect();
+        assert_eq!(expressions.len(), 1);
+        let values: Vec<&str> = expressions[0].iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["x", "+", "1"]);
+        assert_eq!(expressions[0][1].kind, TokenType::Operator);
+
+        let tokens = parser.parse("\"${oops\" x").unwrap();
+        assert!(parser.interpolate(&tokens[0]).is_err());
+        assert!(parser.interpolate(&tokens[1]).is_err());
+    }
+
+    #[test]
+    fn test_group_by_kind() {
+        let tokens = MinimalParser::new().parse("if x + 5 y").unwrap();
+        let groups = group_by(&tokens, |t| t.kind.clone());
+
+        let values = |kind: TokenType| -> Vec<&str> {
+            groups[&kind].iter().map(|t| t.value.as_str()).collect()
+        };
+        assert_eq!(groups.len(), 4);
+        assert_eq!(values(TokenType::Keyword), ["if"]);
+        assert_eq!(values(TokenType::Identifier), ["x", "y"]);
+        assert_eq!(values(TokenType::Operator), ["+"]);
+        assert_eq!(values(TokenType::Literal), ["5"]);
+
+        let by_line = group_by(&tokens, |t| t.line);
+        assert_eq!(by_line[&1].len(), 5);
+    }
+
+    #[test]
+    fn test_columns_are_one_based_character_offsets() {
+        let tokens = MinimalParser::new().parse("    if  x\n\tif\t\tx").unwrap();
+        let positions: Vec<(usize, usize)> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(positions, [(1, 5), (1, 9), (2, 2), (2, 6)]);
+    }
+
+    #[test]
+    fn test_quoted_string_with_spaces_is_one_token() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse(r#"name = "hello world""#).unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[2].kind, TokenType::StringLiteral);
+        assert_eq!(tokens[2].value, "hello world");
+
+        let tokens = parser.parse(r#"say "quote \" inside""#).unwrap();
+        assert_eq!(tokens[1].value, "quote \" inside");
+
+        match parser.parse("ok\nx = \"open") {
+            Err(ParserError::SyntaxError {
+                line,
+                column,
+                message,
+                ..
+            }) => {
+                assert_eq!((line, column), (2, 5));
+                assert!(message.contains("unterminated"));
+            }
+            other => panic!("expected unterminated string error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_doc_comments_are_distinct_from_comments() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            line_comment: Some("//".to_string()),
+            doc_comment: Some("///".to_string()),
+            doc_block_comment: Some(("/**".to_string(), "*/".to_string())),
+            ..ParserConfig::default()
+        });
+
+        let tokens = parser
+            .parse("/// doc\n// plain\nx /** inline */ y")
+            .unwrap();
+        let kinds: Vec<&TokenType> = tokens.iter().map(|t| &t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &TokenType::DocComment,
+                &TokenType::Comment,
+                &TokenType::Identifier,
+                &TokenType::DocComment,
+                &TokenType::Identifier,
+            ]
+        );
+        assert_eq!(
+            (tokens[0].value.as_str(), tokens[1].value.as_str()),
+            ("doc", "plain")
+        );
+        assert_eq!((tokens[3].value.as_str(), tokens[4].column), ("inline", 17));
+
+        let input = "a /** first\n  second */ b";
+        let tokens = parser.parse(input).unwrap();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["a", "first\n  second", "b"]);
+        assert_eq!((tokens[1].line, tokens[1].column), (1, 3));
+        assert_eq!(
+            &input[tokens[1].span.start..tokens[1].span.end],
+            "/** first\n  second */"
+        );
+        assert_eq!(
+            (tokens[2].line, tokens[2].column, tokens[2].span.start),
+            (2, 13, 24)
+        );
+
+        assert!(parser.parse("/** never closed\nx").is_err());
+    }
+
+    #[test]
+    fn test_glued_operators_and_brackets_split() {
+        let parser = MinimalParser::new();
+        let lex = |input: &str| -> Vec<(TokenType, String, usize)> {
+            parser
+                .parse(input)
+                .unwrap()
+                .into_iter()
+                .map(|t| (t.kind, t.value, t.column))
+                .collect()
+        };
+        let tok = |kind: TokenType, value: &str, column: usize| (kind, value.to_string(), column);
+
+        assert_eq!(
+            lex("x+5"),
+            [
+                tok(TokenType::Identifier, "x", 1),
+                tok(TokenType::Operator, "+", 2),
+                tok(TokenType::Literal, "5", 3),
+            ]
+        );
+        assert_eq!(
+            lex("a*b-c"),
+            [
+                tok(TokenType::Identifier, "a", 1),
+                tok(TokenType::Operator, "*", 2),
+                tok(TokenType::Identifier, "b", 3),
+                tok(TokenType::Operator, "-", 4),
+                tok(TokenType::Identifier, "c", 5),
+            ]
+        );
+        assert_eq!(
+            lex("if(x)"),
+            [
+                tok(TokenType::Keyword, "if", 1),
+                tok(TokenType::Punctuation, "(", 3),
+                tok(TokenType::Identifier, "x", 4),
+                tok(TokenType::Punctuation, ")", 5),
+            ]
+        );
+        assert_eq!(lex("  f(3.14)  ;")[2], tok(TokenType::Literal, "3.14", 5));
+        assert_eq!(lex("a   b").len(), 2);
+    }
+
+    #[test]
+    fn test_skip_unrecognized_runs() {
+        let input = "GET /x \u{0}\u{1}\u{fffd}\u{7f}( 200";
+        let noisy = MinimalParser::new().parse(input).unwrap();
+        assert!(
+            noisy
+                .iter()
+                .filter(|t| t.kind == TokenType::Punctuation)
+                .count()
+                > 4
+        );
+
+        let mut config = ParserConfig {
+            skip_unrecognized: true,
+            ..ParserConfig::default()
+        };
+        let values = |config: &ParserConfig| -> Vec<String> {
+            let parser = MinimalParser::with_config(config.clone());
+            parser
+                .parse(input)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.value)
+                .collect()
+        };
+        assert_eq!(values(&config), ["GET", "/", "x", "(", "200"]);
+
+        config.unknown_tokens = true;
+        let tokens = MinimalParser::with_config(config).parse(input).unwrap();
+        assert_eq!(tokens[3].kind, TokenType::Unknown);
+        assert_eq!(tokens[3].value, "\u{0}\u{1}\u{fffd}\u{7f}");
+        assert_eq!((tokens[4].value.as_str(), tokens[4].column), ("(", 12));
+    }
+
+    #[test]
+    fn test_multi_character_operators_match_longest() {
+        let tokens = MinimalParser::new().parse("a >= b && c").unwrap();
+        let operators: Vec<&str> = tokens
+            .iter()
+            .filter(|t| t.kind == TokenType::Operator)
+            .map(|t| t.value.as_str())
+            .collect();
+        assert_eq!(operators, [">=", "&&"]);
+
+        let tokens = MinimalParser::new().parse("x==y !z<w").unwrap();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["x", "==", "y", "!", "z", "<", "w"]);
+        assert!(["=", "<", ">", "!"]
+            .iter()
+            .all(|op| { MinimalParser::new().classify_token(op) == TokenType::Operator }));
+    }
+
+    #[test]
+    fn test_find_repeated_operators() {
+        let parser = MinimalParser::new();
+        assert_eq!(
+            find_repeated_operators(&parser.parse("a ++ b").unwrap()),
+            [1]
+        );
+        assert!(find_repeated_operators(&parser.parse("a + b").unwrap()).is_empty());
+        assert!(find_repeated_operators(&parser.parse("a + + b").unwrap()).is_empty());
+        // `==` is registered, so it lexes as a single operator.
+        assert!(find_repeated_operators(&parser.parse("a == b").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_with_keywords_replaces_defaults() {
+        let parser = MinimalParser::with_keywords(["fn", "let"].map(String::from));
+        assert_eq!(parser.classify_token("fn"), TokenType::Keyword);
+        assert_eq!(parser.classify_token("let"), TokenType::Keyword);
+        assert_eq!(parser.classify_token("if"), TokenType::Identifier);
+
+        assert_eq!(
+            MinimalParser::new().classify_token("if"),
+            TokenType::Keyword
+        );
+    }
+
+    #[test]
+    fn test_spans_slice_source_text() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            line_comment: Some("#".to_string()),
+            doc_block_comment: Some(("/**".to_string(), "*/".to_string())),
+            string_continuation: true,
+            ..ParserConfig::default()
+        });
+        let input = "let naïve = \"a\\\"b\" + 3.5 # note\n\tif(x>=1) /** doc\nmore */ y";
+        let tokens = parser.parse(input).unwrap();
+        assert!(tokens.len() > 10);
+        assert_eq!(tokens.last().unwrap().line, 3);
+
+        for input in [
+            input,
+            "s = \"a\\\r\nb\" x",
+            "s = \"a\\\rb\\\nc\" x",
+            "/** doc\r\n\r\nmore */ y",
+        ] {
+            let tokens = parser.parse(input).unwrap();
+            for token in &tokens {
+                assert_eq!(
+                    &input[token.span.start..token.span.end],
+                    token.source_text(),
+                    "{:?}",
+                    input
+                );
+            }
+        }
+        let tokens = parser.parse("s = \"a\\\r\nb\" x").unwrap();
+        assert_eq!(tokens[2].value, "ab");
+        assert_eq!(tokens[2].raw.as_deref(), Some("\"a\\\r\nb\""));
+    }
+
+    #[test]
+    fn test_parse_range_reports_document_positions() {
+        let parser = MinimalParser::new();
+        let input = "first line\nlet x = 1\nif y >= 2\nlast";
+        let full = parser.parse(input).unwrap();
+
+        let start = input.find("x =").unwrap();
+        let end = input.find("last").unwrap();
+        let partial = parser.parse_range(input, start..end).unwrap();
+
+        let expected: Vec<&Token> = full
+            .iter()
+            .filter(|t| t.span.start >= start && t.span.end <= end)
+            .collect();
+        assert_eq!(partial.len(), expected.len());
+        for (got, want) in partial.iter().zip(expected) {
+            assert_eq!(
+                (&got.value, got.line, got.column),
+                (&want.value, want.line, want.column)
+            );
+            assert_eq!(got.span, want.span);
+        }
+
+        let err = parser.parse_range("ok\nab \"open", 4..11).unwrap_err();
+        assert!(matches!(
+            err,
+            ParserError::SyntaxError {
+                line: 2,
+                column: 4,
+                ..
+            }
+        ));
+        assert!(parser.parse_range(input, 0..input.len() + 1).is_err());
+    }
+
+    #[test]
+    fn test_token_memory_usage_lower_bound() {
+        let tokens = MinimalParser::new()
+            .parse("alpha + \"beta gamma\" 42")
+            .unwrap();
+        let text_bytes: usize = tokens.iter().map(|t| t.value.len()).sum::<usize>()
+            + tokens
+                .iter()
+                .filter_map(|t| t.raw.as_ref())
+                .map(String::len)
+                .sum::<usize>();
+
+        let usage = token_memory_usage(&tokens);
+        assert!(usage >= tokens.len() * std::mem::size_of::<Token>() + text_bytes);
+        assert_eq!(text_bytes, 5 + 1 + 10 + 12 + 2);
+        assert_eq!(token_memory_usage::<()>(&[]), 0);
+    }
+
+    #[test]
+    fn test_signed_and_decimal_numbers() {
+        let parser = MinimalParser::new();
+        assert_eq!(parser.classify_token("3.14"), TokenType::Literal);
+        assert_eq!(parser.classify_token(".5"), TokenType::Literal);
+        assert_eq!(parser.classify_token("42."), TokenType::Literal);
+        assert_eq!(parser.classify_token("-7"), TokenType::Literal);
+        assert_eq!(parser.classify_token("+2.5"), TokenType::Literal);
+        assert_eq!(parser.classify_token("3.14.15"), TokenType::Punctuation);
+        assert_eq!(parser.classify_token("--7"), TokenType::Punctuation);
+
+        let config = ParserConfig::default();
+        assert!(config.is_float_literal("3.14") && config.is_float_literal("+2.5"));
+        assert!(!config.is_float_literal("-7") && !config.is_float_literal("3.14.15"));
+        let floats = ParserConfig {
+            float_tokens: true,
+            ..ParserConfig::default()
+        };
+        assert_eq!(floats.classify("+2.5"), TokenType::Float);
+        assert_eq!(floats.classify("-7"), TokenType::Literal);
+
+        let values: Vec<String> = parser
+            .parse("x = -7 + .5")
+            .unwrap()
+            .into_iter()
+            .map(|t| t.value)
+            .collect();
+        assert_eq!(values, ["x", "=", "-", "7", "+", ".5"]);
+
+        match parser.parse("v = 3.14.15") {
+            Err(ParserError::SyntaxError {
+                line,
+                column,
+                message,
+                ..
+            }) => {
+                assert_eq!((line, column), (1, 5));
+                assert!(message.contains("3.14.15"));
+            }
+            other => panic!("expected malformed number error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unicode_line_separators() {
+        let input = "a\u{2028}b";
+        let positions = |parser: MinimalParser| -> Vec<(usize, usize)> {
+            parser
+                .parse(input)
+                .unwrap()
+                .iter()
+                .map(|t| (t.line, t.column))
+                .collect()
+        };
+
+        assert_eq!(positions(MinimalParser::new()), [(1, 1), (1, 3)]);
+
+        let parser = MinimalParser::with_config(ParserConfig {
+            unicode_line_separators: true,
+            ..ParserConfig::default()
+        });
+        let tokens = parser.parse(input).unwrap();
+        assert_eq!(tokens[1].span, Span::new(4, 5));
+        assert_eq!(positions(parser), [(1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn test_hex_and_binary_literals() {
+        let parser = MinimalParser::new();
+        assert_eq!(parser.classify_token("0xFF"), TokenType::Literal);
+        assert_eq!(parser.classify_token("0XfF"), TokenType::Literal);
+        assert_eq!(parser.classify_token("0b1010"), TokenType::Literal);
+        assert_eq!(parser.classify_token("0B1"), TokenType::Literal);
+
+        let tokens = parser.parse("mov 0xFF 0b1010").unwrap();
+        assert_eq!(tokens[1].value, "0xFF");
+        assert_eq!(tokens[2].kind, TokenType::Literal);
+
+        let column = |input: &str| match parser.parse(input) {
+            Err(ParserError::SyntaxError { column, .. }) => column,
+            other => panic!("expected syntax error, got {:?}", other),
+        };
+        assert_eq!(column("x 0xG1"), 5);
+        assert_eq!(column("x 0x"), 5);
+        assert_eq!(column("0b102"), 5);
+    }
+
+    #[test]
+    fn test_replace_value() {
+        let mut tokens = MinimalParser::new().parse("total = n + \"s\"").unwrap();
+        tokens[0].replace_value("sum".to_string());
+        assert_eq!(tokens[0].value, "sum");
+        assert_eq!(tokens[0].span, Span::new(0, 3));
+        assert_eq!(tokens[0].span.len(), 3);
+        assert_eq!(tokens[1].span, Span::new(6, 7));
+
+        tokens[4].replace_value("text".to_string());
+        assert_eq!(tokens[4].source_text(), "text");
+        assert_eq!(tokens[4].span, Span::new(12, 16));
+    }
+
+    #[test]
+    fn test_comment_prefix_skipping() {
+        let parser = MinimalParser::with_comment_prefix("#");
+        let tokens = parser.parse("x = 1 # this is ignored").unwrap();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["x", "=", "1"]);
+        assert_eq!(tokens[2].column, 5);
+
+        let tokens = parser.parse("s = \"a # b\" # note\ny").unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[2].value, "a # b");
+        assert_eq!((tokens[3].line, tokens[3].column), (2, 1));
+
+        let plain = MinimalParser::new()
+            .parse("x = 1 # this is ignored")
+            .unwrap();
+        assert!(plain.iter().any(|t| t.value == "ignored"));
+    }
+
+    #[test]
+    fn test_reflow_spans() {
+        let source = "let x = x + 1";
+        let mut tokens = MinimalParser::new().parse(source).unwrap();
+        let old = tokens[1].span;
+        tokens[1].replace_value("count".to_string());
+        let delta = tokens[1].span.len() as isize - old.len() as isize;
+        reflow_spans(&mut tokens, old.end, delta);
+
+        let edited = "let count = x + 1";
+        assert!(tokens
+            .iter()
+            .all(|t| &edited[t.span.start..t.span.end] == t.source_text()));
+        assert_eq!(tokens[0].span, Span::new(0, 3));
+        assert_eq!(tokens[5].span, Span::new(16, 17));
+
+        reflow_spans(&mut tokens, 4, -4);
+        assert_eq!(tokens[0].span, Span::new(0, 3));
+        assert_eq!(tokens[1].span, Span::new(0, 5));
+    }
+
+    #[test]
+    fn test_streaming_tokens() {
+        let mut input = String::from("first second\n");
+        input.push_str(&"filler words\n".repeat(10_000));
+        input.push_str("\"unterminated");
+
+        let parser = MinimalParser::new();
+        let head: Vec<Token> = parser
+            .tokens(&input)
+            .take(2)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let values: Vec<&str> = head.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["first", "second"]);
+        assert!(parser.parse(&input).is_err());
+
+        let mut stream = parser.tokens("a\n\"b");
+        assert_eq!(stream.next().unwrap().unwrap().value, "a");
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_raw_identifier_prefix() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            raw_identifier_prefix: Some("r#".to_string()),
+            ..ParserConfig::default()
+        });
+        let tokens = parser.parse("if r#if r#").unwrap();
+        assert_eq!(tokens[0].kind, TokenType::Keyword);
+        assert_eq!(tokens[1].kind, TokenType::Identifier);
+        assert_eq!(tokens[1].value, "if");
+        assert_eq!(tokens[1].source_text(), "r#if");
+        assert_eq!(tokens[1].span, Span::new(3, 7));
+        assert_eq!(tokens[2].value, "r");
+
+        let plain = MinimalParser::new().parse("r#if").unwrap();
+        assert_eq!(plain[0].value, "r");
+    }
+
+    #[test]
+    fn test_parse_with_progress_callback() {
+        let input = "a = b + 1\n".repeat(1_000);
+        let mut reports = Vec::new();
+        let tokens = MinimalParser::new()
+            .parse_with_progress_callback(&input, |fraction| reports.push(fraction))
+            .unwrap();
+
+        assert_eq!(tokens.len(), 5_000);
+        assert_eq!(reports.len(), 5_000 / PROGRESS_INTERVAL + 1);
+        assert!(reports.windows(2).all(|w| w[0] <= w[1]));
+        assert!(reports[0] > 0.0);
+        assert!(reports[reports.len() - 2] > 0.95);
+        assert_eq!(reports.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn test_io_error_source() {
+        let parser = MinimalParser::new();
+        let invalid_utf8: &[u8] = b"ok\n\xff\xfe\n";
+        let results: Vec<_> = parser.tokenize_reader_lines(invalid_utf8).collect();
+        assert_eq!(results[0].as_ref().unwrap()[0].value, "ok");
+        let error = results[1].as_ref().unwrap_err();
+        assert!(matches!(error, ParserError::Io(_)));
+        assert!(error
+            .to_string()
+            .starts_with("I/O error while reading input"));
+
+        let wrapped = ParserError::from(std::io::Error::other("disk gone"));
+        assert_eq!(wrapped.source().unwrap().to_string(), "disk gone");
+        assert!(ParserError::UnexpectedToken("x".to_string())
+            .source()
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_resilient() {
+        let input = "a = 1\nb = \"open\nc = 2\nd = 3.14.15 + e\nf";
+        let (tokens, errors) = MinimalParser::new().parse_resilient(input);
+
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(
+            values,
+            ["a", "=", "1", "b", "=", "open", "c", "=", "2", "d", "=", "f"]
+        );
+        let lines: Vec<usize> = errors
+            .iter()
+            .map(|e| match e {
+                ParserError::SyntaxError { line, .. } => *line,
+                other => panic!("unexpected error {:?}", other),
+            })
+            .collect();
+        assert_eq!(lines, [2, 4]);
+        assert_eq!(tokens[11].line, 5);
+
+        let (tokens, errors) = MinimalParser::new().parse_resilient("x y");
+        assert_eq!((tokens.len(), errors.len()), (2, 0));
+    }
+
+    #[test]
+    fn test_to_snapshot_string() {
+        let tokens = MinimalParser::new()
+            .parse("x  foo\n\"a\\nb\\t\\\"q\\\"\"")
+            .unwrap();
+        assert_eq!(tokens[1].to_snapshot_string(), "Identifier(\"foo\")@1:4");
+        assert_eq!(tokens[2].value, "a\nb\t\"q\"");
+        assert_eq!(
+            tokens[2].to_snapshot_string(),
+            r#"StringLiteral("a\nb\t\"q\"")@2:1"#
+        );
+    }
+
+    #[test]
+    fn test_keyword_phrases() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            keyword_phrases: vec!["else if".to_string()],
+            ..ParserConfig::default()
+        });
+        let tokens = parser
+            .parse("else if x\nelse y\nelse  if\nelse iffy")
+            .unwrap();
+        let snapshot: Vec<String> = tokens.iter().map(Token::to_snapshot_string).collect();
+        assert_eq!(
+            snapshot,
+            [
+                "Keyword(\"else if\")@1:1",
+                "Identifier(\"x\")@1:9",
+                "Keyword(\"else\")@2:1",
+                "Identifier(\"y\")@2:6",
+                "Keyword(\"else if\")@3:1",
+                "Keyword(\"else\")@4:1",
+                "Identifier(\"iffy\")@4:6",
+            ]
+        );
+        assert_eq!(tokens[0].span, Span::new(0, 7));
+        assert_eq!(tokens[4].source_text(), "else  if");
+    }
+
+    #[test]
+    fn test_eof_token() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            emit_eof: true,
+            ..ParserConfig::default()
+        });
+
+        let tokens = parser.parse("x = 1\ny\n").unwrap();
+        let eof = tokens.last().unwrap();
+        assert_eq!(eof.kind, TokenType::Eof);
+        assert_eq!((eof.line, eof.column), (3, 1));
+        assert_eq!(eof.span, Span::new(8, 8));
+
+        let tokens = parser.parse("ab").unwrap();
+        assert_eq!(tokens.last().unwrap().to_snapshot_string(), "Eof(\"\")@1:3");
+        assert_eq!(parser.parse("").unwrap().len(), 1);
+        assert_eq!(MinimalParser::new().parse("ab").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parser_error_into_io_error() {
+        let error = ParserError::SyntaxError {
+            line: 2,
+            column: 5,
+            message: "unterminated string literal".to_string(),
+            snippet: String::new(),
+        };
+        let io: std::io::Error = error.into();
+        assert_eq!(io.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            io.to_string(),
+            "Syntax Error at line 2, column 5: unterminated string literal"
+        );
+
+        let wrapped = ParserError::Io(std::io::ErrorKind::NotFound.into());
+        assert_eq!(
+            std::io::Error::from(wrapped).kind(),
+            std::io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_lossless_round_trip() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            lossless: true,
+            ..ParserConfig::default()
+        });
+        let input = "if x {\n\tname = \"a b\"  + 1\r\n\n  }\n";
+        let tokens = parser.parse(input).unwrap();
+
+        let rebuilt: String = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(rebuilt, input);
+        let line_breaks: Vec<&str> = tokens
+            .iter()
+            .filter(|t| t.kind == TokenType::Whitespace && t.value.contains('\n'))
+            .map(|t| t.value.as_str())
+            .collect();
+        assert_eq!(line_breaks, ["\n", "\r\n\n", "\n"]);
+        assert_eq!(tokens[3].to_snapshot_string(), "Whitespace(\" \")@1:5");
+        assert_eq!(tokens[6].value, "\t");
+        assert_eq!(tokens[11].value, "\"a b\"");
+
+        let default = MinimalParser::new().parse(input).unwrap();
+        assert!(default.iter().all(|t| t.kind != TokenType::Whitespace));
+    }
+
+    #[test]
+    fn test_require_min_tokens() {
+        let parser = MinimalParser::new();
+        match parser.require_min_tokens("x y", 3) {
+            Err(ParserError::SyntaxError {
+                line,
+                column,
+                message,
+                ..
+            }) => {
+                assert_eq!((line, column), (1, 4));
+                assert_eq!(message, "expected at least 3 tokens, found 2");
+            }
+            other => panic!("expected too-few-tokens error, got {:?}", other),
+        }
+        assert_eq!(parser.require_min_tokens("x y z", 3).unwrap().len(), 3);
+        assert!(parser.require_min_tokens("\"open", 0).is_err());
+    }
+
+    #[test]
+    fn test_token_at_position() {
+        let tokens = MinimalParser::new()
+            .parse("count = 42\n  if \"a b\"")
+            .unwrap();
+        let at = |line, column| token_at_position(&tokens, line, column).map(|t| t.value.as_str());
+
+        assert_eq!(at(1, 1), Some("count"));
+        assert_eq!(at(1, 5), Some("count"));
+        assert_eq!(at(1, 6), None);
+        assert_eq!(at(1, 7), Some("="));
+        assert_eq!(at(1, 10), Some("42"));
+        assert_eq!(at(2, 1), None);
+        assert_eq!(at(2, 4), Some("if"));
+        assert_eq!(at(2, 10), Some("a b"));
+        assert_eq!(at(2, 11), None);
+        assert_eq!(at(3, 1), None);
+    }
+
+    #[test]
+    fn test_columns_count_chars_not_bytes() {
+        let parser = MinimalParser::new();
+        let tokens = parser.parse("café x\n🦀 \"naïve\" y").unwrap();
+        let positions: Vec<(usize, usize)> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(positions, [(1, 1), (1, 6), (2, 1), (2, 3), (2, 11)]);
+        assert_eq!(tokens[1].span, Span::new(6, 7));
+        assert_eq!(tokens[4].span, Span::new(22, 23));
+
+        match parser.parse("é = \"open") {
+            Err(ParserError::SyntaxError { column, .. }) => assert_eq!(column, 5),
+            other => panic!("expected unterminated string, got {:?}", other),
+        }
+
+        let csv = MinimalParser::with_config(ParserConfig {
+            delimited: Some(DelimitedMode {
+                separator: ',',
+                escape: '\\',
+            }),
+            ..ParserConfig::default()
+        });
+        let fields = csv.parse("ü,x").unwrap();
+        assert_eq!((fields[1].column, fields[1].span), (3, Span::new(3, 4)));
+    }
+
+    #[test]
+    fn test_string_continuation() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            string_continuation: true,
+            ..ParserConfig::default()
+        });
+        let input = "s = \"a\\\nb\" x\ny \"c\\\\\"";
+        let tokens = parser.parse(input).unwrap();
+        let snapshot: Vec<String> = tokens.iter().map(Token::to_snapshot_string).collect();
+        assert_eq!(
+            snapshot,
+            [
+                "Identifier(\"s\")@1:1",
+                "Operator(\"=\")@1:3",
+                "StringLiteral(\"ab\")@1:5",
+                "Identifier(\"x\")@2:4",
+                "Identifier(\"y\")@3:1",
+                "StringLiteral(\"c\\\\\")@3:3",
+            ]
+        );
+        assert_eq!(tokens[2].source_text(), "\"a\\\nb\"");
+        assert_eq!(tokens[2].span, Span::new(4, 10));
+        assert_eq!(tokens[3].span, Span::new(11, 12));
+
+        assert!(parser.parse("\"a\\\\\nb\"").is_err());
+        assert!(parser.parse("\"a\\\nb").is_err());
+        assert!(MinimalParser::new().parse("\"a\\\nb\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let path = std::env::temp_dir().join(format!("battle_tested_{}.src", std::process::id()));
+        std::fs::write(&path, "if x\n  y = 1\n").unwrap();
+        let tokens = MinimalParser::new().parse_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(tokens.unwrap().len(), 5);
+
+        let missing = std::env::temp_dir().join("battle_tested_missing/nothing.src");
+        let error = MinimalParser::new().parse_file(&missing).unwrap_err();
+        assert!(matches!(&error, ParserError::Io(e) if e.kind() == std::io::ErrorKind::NotFound));
+        assert!(error.to_string().contains(&missing.display().to_string()));
+    }
+
+    #[test]
+    fn test_token_equality() {
+        let token = |kind, value: &str, column, start, raw: Option<&str>| Token {
+            kind,
+            value: value.to_string(),
+            line: 1,
+            column,
+            raw: raw.map(str::to_string),
+            span: Span::new(start, start + raw.unwrap_or(value).len()),
+            payload: (),
+        };
+        let expected = vec![
+            token(TokenType::Keyword, "if", 1, 0, None),
+            token(TokenType::Identifier, "x", 4, 3, None),
+            token(TokenType::StringLiteral, "y", 6, 5, Some("\"y\"")),
+        ];
+        let tokens = MinimalParser::new().parse("if x \"y\"").unwrap();
+        assert_eq!(tokens, expected);
+        assert_ne!(tokens[..1], expected[1..2]);
+    }
+
+    #[test]
+    fn test_config_diff() {
+        let default = ParserConfig::default();
+        let mut extended = default.clone();
+        extended.keywords.insert("for".to_string());
+        assert_eq!(
+            default.diff(&extended),
+            [ConfigDifference::Keywords {
+                added: vec!["for".to_string()],
+                removed: vec![]
+            }]
+        );
+        assert!(default.diff(&default.clone()).is_empty());
+
+        extended.lossless = true;
+        extended.sigils.extend(['@', '$']);
+        let diffs = default.diff(&extended);
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(
+            diffs[1],
+            ConfigDifference::Flag {
+                name: "lossless",
+                left: false,
+                right: true
+            }
+        );
+        assert_eq!(
+            diffs[2],
+            ConfigDifference::Setting {
+                name: "sigils",
+                left: "{}".to_string(),
+                right: "{'$', '@'}".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_config_builder() {
+        let config = ParserConfig::builder()
+            .keywords(["fn", "let"])
+            .line_comment("#")
+            .keep_comments(false)
+            .emit_eof(true)
+            .build();
+        let parser = MinimalParser::from_config(config);
+        let tokens = parser.parse("let x = if # trailing words").unwrap();
+
+        let kinds: Vec<TokenType> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenType::Keyword,
+                TokenType::Identifier,
+                TokenType::Operator,
+                TokenType::Identifier,
+                TokenType::Eof,
+            ]
+        );
+        assert!(ParserConfig::builder()
+            .build()
+            .diff(&ParserConfig::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_verify_cache() {
+        let source = "if ready\n  launch = \"now\"";
+        let fingerprint = fingerprint_tokens(&MinimalParser::new().parse(source).unwrap());
+
+        assert!(verify_cache(source, fingerprint));
+        assert!(!verify_cache("if ready\n  launch = \"later\"", fingerprint));
+        assert!(!verify_cache("if  ready\n  launch = \"now\"", fingerprint));
+        assert!(!verify_cache("\"unterminated", fingerprint));
+        assert_ne!(fingerprint_tokens(&[]), fingerprint);
+    }
+
+    #[test]
+    fn test_operator_aliases() {
+        let mut config = ParserConfig::default();
+        config
+            .operator_aliases
+            .insert("and".to_string(), "&&".to_string());
+        config
+            .operator_aliases
+            .insert("<>".to_string(), "!=".to_string());
+        config.operators.insert("<>".to_string());
+        let parser = MinimalParser::with_config(config);
+
+        let tokens = parser.parse("a and b <> \"and\" && band").unwrap();
+        let snapshot: Vec<String> = tokens.iter().map(Token::to_snapshot_string).collect();
+        assert_eq!(
+            snapshot,
+            [
+                "Identifier(\"a\")@1:1",
+                "Operator(\"&&\")@1:3",
+                "Identifier(\"b\")@1:7",
+                "Operator(\"!=\")@1:9",
+                "StringLiteral(\"and\")@1:12",
+                "Operator(\"&&\")@1:18",
+                "Identifier(\"band\")@1:21",
+            ]
+        );
+        assert_eq!(tokens[1].raw.as_deref(), Some("and"));
+        assert_eq!(tokens[5].raw, None);
+        assert_eq!(parser.classify_token("and"), TokenType::Operator);
+    }
+
+    #[test]
+    fn test_identifiers_with_digits_and_underscores() {
+        let parser = MinimalParser::new();
+        for word in ["foo_bar", "_tmp", "x1", "_", "été2"] {
+            assert_eq!(
+                parser.classify_token(word),
+                TokenType::Identifier,
+                "{}",
+                word
+            );
+        }
+        assert_ne!(parser.classify_token("1x"), TokenType::Identifier);
+        assert_eq!(parser.classify_token("123"), TokenType::Literal);
+
+        let tokens = parser.parse("x1 = foo_bar + 1x").unwrap();
+        let kinds: Vec<TokenType> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenType::Identifier,
+                TokenType::Operator,
+                TokenType::Identifier,
+                TokenType::Operator,
+                TokenType::Punctuation,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relative_to() {
+        let input = "skip me\nkeep x\n  y = 1\nskip";
+        let range = 8..22;
+        let tokens = MinimalParser::new()
+            .parse_range(input, range.clone())
+            .unwrap();
+        assert_eq!(
+            (tokens[2].line, tokens[2].column, tokens[2].span),
+            (3, 3, Span::new(17, 18))
+        );
+
+        let relative = tokens[2].relative_to(range.start);
+        assert_eq!(relative.span, Span::new(9, 10));
+        assert_eq!((relative.line, relative.column), (3, 3));
+
+        let slice = SourceBuffer::new(&input[range.clone()]);
+        let relative: Vec<Token> = tokens
+            .iter()
+            .map(|t| t.relative_to_buffer(range.start, &slice))
+            .collect();
+        let positions: Vec<(usize, usize)> = relative.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(positions, [(1, 1), (1, 6), (2, 3), (2, 5), (2, 7)]);
+        let text = &input[range];
+        assert!(relative
+            .iter()
+            .all(|t| text[t.span.start..t.span.end] == t.value));
+    }
+
+    #[test]
+    fn test_syntax_error_snippet() {
+        let parser = MinimalParser::new();
+        let error = parser.parse("x = 1\nlet s = \"abc").unwrap_err();
+        match &error {
+            ParserError::SyntaxError {
+                line: 2,
+                column: 9,
+                snippet,
+                ..
+            } => {
+                assert_eq!(snippet, "let s = \"abc");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let rendered = error.to_string();
+        assert!(
+            rendered.ends_with("\nlet s = \"abc\n        ^"),
+            "{}",
+            rendered
+        );
+
+        // Long lines are cut around the column, and the caret moves with them.
+        let long = format!("{}\"open", "a ".repeat(100));
+        let rendered = parser.parse(&long).unwrap_err().to_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines[1].starts_with("...") && lines[1].ends_with("\"open"));
+        assert_eq!(lines[1].find('"'), lines[2].find('^'));
+    }
+
+    #[test]
+    fn test_run_benchmark() {
+        let inputs = ["hello world", "if x + 5 > 10 { y }", "a b c"];
+        let results = run_benchmark(&MinimalParser::new(), &inputs, false);
+        assert_eq!(results.len(), 3);
+        for (result, input) in results.iter().zip(inputs) {
+            assert!(result.token_count > 0);
+            assert_eq!(result.byte_count, input.len());
+            assert!(result.tokens_per_sec() >= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_normalize_newlines() {
+        assert_eq!(normalize_newlines("a\r\nb\rc\nd"), "a\nb\nc\nd");
+        assert!(matches!(
+            normalize_newlines("a\nb\n"),
+            Cow::Borrowed("a\nb\n")
+        ));
+
+        let config = ParserConfig {
+            normalize_newlines: true,
+            ..ParserConfig::default()
+        };
+        let tokens = MinimalParser::with_config(config)
+            .parse("a\rb\r\nc")
+            .unwrap();
+        let lines: Vec<usize> = tokens.iter().map(|t| t.line).collect();
+        assert_eq!(lines, [1, 2, 3]);
+        assert_eq!(tokens[2].span, Span::new(4, 5));
+    }
+
+    #[test]
+    fn test_normalized_spans_agree_across_entry_points() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            normalize_newlines: true,
+            ..ParserConfig::default()
+        });
+        let input = "a\r\nb = \"c\"\r\n\r\nd";
+        let spans = |tokens: &[Token]| tokens.iter().map(|t| t.span).collect::<Vec<_>>();
+        let expected = spans(&parser.parse(input).unwrap());
+        assert_eq!(expected[1], Span::new(2, 3));
+
+        let streamed: Vec<Token> = parser.tokens(input).map(Result::unwrap).collect();
+        assert_eq!(spans(&streamed), expected);
+        assert_eq!(
+            spans(&parser.parse_first_n(input, 2).unwrap()),
+            expected[..2]
+        );
+        assert_eq!(spans(&parser.analyze(input).tokens), expected);
+        assert_eq!(spans(&parser.parse_resilient(input).0), expected);
+        let read: Vec<Token> = parser
+            .tokenize_reader_lines(input.as_bytes())
+            .flat_map(Result::unwrap)
+            .collect();
+        assert_eq!(spans(&read), expected);
+
+        let lossless = MinimalParser::with_config(ParserConfig {
+            normalize_newlines: true,
+            lossless: true,
+            ..ParserConfig::default()
+        });
+        let text: String = lossless.tokens(input).map(|t| t.unwrap().value).collect();
+        assert_eq!(text, "a\nb = \"c\"\n\nd");
+    }
+
+    #[cfg(feature = "rowan")]
+    #[test]
+    fn test_rowan_green_tree_round_trip() {
+        let config = ParserConfig {
+            lossless: true,
+            line_comment: Some("//".to_string()),
+            ..ParserConfig::default()
+        };
+        let source = "if x  + 5 // note\n\twhile \"y z\"\n";
+        let tokens = MinimalParser::with_config(config).parse(source).unwrap();
+
+        let green = build_green_tree(&tokens);
+        assert_eq!(green.kind(), ROWAN_ROOT);
+        assert_eq!(rowan_kind(&TokenType::Identifier), rowan::SyntaxKind(0));
+        assert_eq!(rowan_kind(&TokenType::Whitespace).0 + 1, ROWAN_ROOT.0);
+        assert_eq!(green.children().len(), tokens.len());
+        assert_eq!(green.to_string(), source);
+    }
+
+    #[test]
+    fn test_crlf_and_lone_cr_line_endings() {
+        let parser = MinimalParser::new();
+        let lf = "if x + 5\nwhile \"y z\"\n\nfoo;\n";
+        let expected = parser.parse(lf).unwrap();
+
+        for ending in ["\r\n", "\r"] {
+            let source = lf.replace('\n', ending);
+            let tokens = parser.parse(&source).unwrap();
+            assert_eq!(tokens.len(), expected.len(), "{:?}", ending);
+            for (token, want) in tokens.iter().zip(&expected) {
+                assert_eq!((&token.kind, &token.value), (&want.kind, &want.value));
+                assert_eq!((token.line, token.column), (want.line, want.column));
+                let spelled = token.raw.as_deref().unwrap_or(&token.value);
+                assert_eq!(&source[token.span.start..token.span.end], spelled);
+            }
+            assert!(tokens.iter().all(|t| !t.value.contains('\r')));
+
+            let buffer = SourceBuffer::new(&source);
+            assert_eq!(buffer.line_count(), 5);
+            assert_eq!(buffer.line(4), Some("foo;"));
+            let streamed: Vec<Token> = parser
+                .tokenize_reader_lines(source.as_bytes())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+                .concat();
+            assert_eq!(streamed, tokens);
+        }
+    }
+
+    #[test]
+    fn test_parse_with_metrics_counts_scans() {
+        let input = "if x = 42 + \"hi\"\ny * 3.5 - z";
+        let (result, metrics) = MinimalParser::new().parse_with_metrics(input);
+        assert_eq!(result.unwrap().len(), 11);
+        assert_eq!(
+            metrics,
+            ScanMetrics {
+                identifier_scans: 4,
+                number_scans: 2,
+                string_scans: 1,
+                operator_scans: 4,
+                bytes_examined: input.len() - 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_custom_classifier() {
+        struct AllKeywords;
+        impl Classifier for AllKeywords {
+            fn classify(&self, _word: &str) -> TokenType {
+                TokenType::Keyword
+            }
+        }
+
+        let parser = MinimalParser::with_classifier(ParserConfig::default(), AllKeywords);
+        let tokens = parser.parse("let x 42 TODO").unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert!(tokens.iter().all(|t| t.kind == TokenType::Keyword));
+        assert_eq!(parser.classify_token("foo"), TokenType::Keyword);
+        // Operators and strings are matched before classification.
+        let tokens = parser.parse("x + \"s\"").unwrap();
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenType::Keyword,
+                TokenType::Operator,
+                TokenType::StringLiteral
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_owned_with_normalized_crlf() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            normalize_newlines: true,
+            ..ParserConfig::default()
+        });
+        let stream = parser
+            .parse_owned("alpha\r\nbeta\r\ngamma".to_string())
+            .unwrap();
+
+        assert_eq!(stream.source(), "alpha\nbeta\ngamma");
+        let texts: Vec<&str> = stream.iter().map(|(_, text)| text).collect();
+        assert_eq!(texts, ["alpha", "beta", "gamma"]);
+    }
+
+    #[test]
+    fn test_find_trailing_commas_skips_trivia() {
+        let parser = MinimalParser::with_config(ParserConfig {
+            lossless: true,
+            line_comment: Some("//".to_string()),
+            ..ParserConfig::default()
+        });
+        let tokens = parser.parse("[1, 2, // last\n ]").unwrap();
+        let commas = find_trailing_commas(&tokens);
+
+        assert_eq!(commas.len(), 1);
+        assert_eq!(tokens[commas[0]].value, ",");
+        assert_eq!(tokens[commas[0]].column, 6);
+        assert!(find_trailing_commas(&parser.parse("[1, 2 ]").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_resilient_skips_unmatched_quote() {
+        // The unmatched quote is dropped and the text after it lexed as code.
+        let (tokens, errors) = MinimalParser::new().parse_resilient("x = \"a + b");
+        let lexed: Vec<(&str, usize)> = tokens
+            .iter()
+            .map(|t| (t.value.as_str(), t.column))
+            .collect();
+        assert_eq!(lexed, [("x", 1), ("=", 3), ("a", 6), ("+", 8), ("b", 10)]);
+        assert_eq!(tokens[2].span, Span::new(5, 6));
+        assert!(matches!(
+            errors[..],
+            [ParserError::SyntaxError {
+                line: 1,
+                column: 5,
+                ..
+            }]
+        ));
+    }
+}
@@ -1,171 +1,42 @@
 use std::time::Instant;
-use std::error::Error;
-use std::fmt;
-
-// Custom Error Handling
-#[derive(Debug)]
-pub enum ParserError {
-    SyntaxError { 
-        line: usize, 
-        column: usize, 
-        message: String 
-    },
-    UnexpectedToken(String),
-}
-
-impl fmt::Display for ParserError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ParserError::SyntaxError { line, column, message } => 
-                write!(f, "Syntax Error at line {}, column {}: {}", line, column, message),
-            ParserError::UnexpectedToken(token) => 
-                write!(f, "Unexpected token: {}", token),
-        }
-    }
-}
-
-impl Error for ParserError {}
-
-// Token Representation
-#[derive(Debug, Clone)]
-pub struct Token {
-    pub kind: TokenType,
-    pub value: String,
-    pub line: usize,
-    pub column: usize,
-}
-
-// Token Type Enumeration
-#[derive(Debug, Clone, PartialEq)]
-pub enum TokenType {
-    Identifier,
-    Literal,
-    Operator,
-    Keyword,
-    Punctuation,
-}
 
-// Recovery Mechanism
-#[derive(Debug)]
-pub enum RecoveryAction {
-    Skip,
-    Replace(Token),
-    Synchronize(usize),
-}
-
-// Trait for Parsing Strategy
-pub trait Parser {
-    fn parse(&self, input: &str) -> Result<Vec<Token>, ParserError>;
-    fn recover_from_error(&self, error: &ParserError) -> Option<RecoveryAction>;
-}
+use battle_tested::{MinimalParser, ParseMetrics, Parser, ParserError, RecoveryAction, Token};
 
 // Benchmark-ready parsing structure
 struct BattleTestedParser {
     input: String,
+    lexer: MinimalParser,
 }
 
 impl BattleTestedParser {
     fn new(input: String) -> Self {
-        Self { input }
-    }
-    
-    fn classify_token(&self, word: &str) -> TokenType {
-        // Basic token classification
-        match word {
-            "+" | "-" | "*" | "/" => TokenType::Operator,
-            "if" | "else" | "while" => TokenType::Keyword,
-            _ if word.chars().all(char::is_alphabetic) => TokenType::Identifier,
-            _ if word.chars().all(char::is_numeric) => TokenType::Literal,
-            _ => TokenType::Punctuation,
+        Self {
+            input,
+            lexer: MinimalParser::new(),
         }
     }
-}
 
-impl Parser for BattleTestedParser {
-    // Simulated parsing method with performance tracking
-    fn parse(&self, _input: &str) -> Result<Vec<Token>, ParserError> {
+    // Parses the stored input and reports how long it took.
+    fn parse_with_metrics(&self) -> Result<(Vec<Token>, ParseMetrics), ParserError> {
         let start = Instant::now();
-        
-        // Use the stored input instead of parameter for backward compatibility
-        let mut tokens = Vec::new();
-        for (line_num, line) in self.input.lines().enumerate() {
-            for (col, word) in line.split_whitespace().enumerate() {
-                let token = Token {
-                    kind: self.classify_token(word),
-                    value: word.to_string(),
-                    line: line_num + 1,
-                    column: col,
-                };
-                tokens.push(token);
-            }
-        }
-        
-        let duration = start.elapsed();
-        println!("Parsing completed in {:?}", duration);
-        
-        Ok(tokens)
-    }
-    
-    fn recover_from_error(&self, error: &ParserError) -> Option<RecoveryAction> {
-        match error {
-            ParserError::SyntaxError { line, column, .. } => 
-                Some(RecoveryAction::Synchronize(*line)),
-            ParserError::UnexpectedToken(_) => 
-                Some(RecoveryAction::Skip),
-        }
+        let tokens = self.lexer.parse(&self.input)?;
+        let metrics = ParseMetrics {
+            duration: start.elapsed(),
+            token_count: tokens.len(),
+            byte_count: self.input.len(),
+        };
+        Ok((tokens, metrics))
     }
 }
 
-// Minimal Parsing Implementation
-#[derive(Debug)]
-pub struct MinimalParser {}
-
-impl Parser for MinimalParser {
-    fn parse(&self, input: &str) -> Result<Vec<Token>, ParserError> {
-        let mut tokens = Vec::new();
-        let mut lines = input.lines().enumerate();
-        
-        // Placeholder parsing logic
-        for (line_num, line) in lines {
-            // Basic token extraction (oversimplified)
-            for (col, word) in line.split_whitespace().enumerate() {
-                let token = Token {
-                    kind: self.classify_token(word),
-                    value: word.to_string(),
-                    line: line_num + 1,
-                    column: col,
-                };
-                tokens.push(token);
-            }
-        }
-        
-        Ok(tokens)
+impl Parser for BattleTestedParser {
+    fn parse(&self, _input: &str) -> Result<Vec<Token>, ParserError> {
+        // Use the stored input instead of parameter for backward compatibility
+        self.lexer.parse(&self.input)
     }
 
     fn recover_from_error(&self, error: &ParserError) -> Option<RecoveryAction> {
-        match error {
-            ParserError::SyntaxError { line, column, .. } => 
-                Some(RecoveryAction::Synchronize(*line)),
-            ParserError::UnexpectedToken(_) => 
-                Some(RecoveryAction::Skip),
-        }
-    }
-}
-
-impl MinimalParser {
-    pub fn new() -> Self {
-        MinimalParser {}
-    }
-
-    pub fn classify_token(&self, token: &str) -> TokenType {
-        // Very basic token classification
-        match token {
-            "+" | "-" | "*" | "/" => TokenType::Operator,
-            "if" | "else" | "while" => TokenType::Keyword,
-            _ if token.chars().all(char::is_alphabetic) => TokenType::Identifier,
-            _ if token.chars().all(char::is_numeric) => TokenType::Literal,
-            _ => TokenType::Punctuation,
-        }
+        self.lexer.recover_from_error(error)
     }
 }
 
@@ -179,11 +50,15 @@ fn main() {
 
     for input in test_inputs {
         let battle_parser = BattleTestedParser::new(input.clone());
-        match battle_parser.parse(&input) {
-            Ok(tokens) => println!("BattleTested Parser: Parsed {} tokens", tokens.len()),
+        match battle_parser.parse_with_metrics() {
+            Ok((tokens, metrics)) => println!(
+                "BattleTested Parser: Parsed {} tokens in {:?}",
+                tokens.len(),
+                metrics.duration
+            ),
             Err(e) => eprintln!("Parsing error: {}", e),
         }
-        
+
         let minimal_parser = MinimalParser::new();
         match minimal_parser.parse(&input) {
             Ok(tokens) => println!("Minimal Parser: Parsed {} tokens", tokens.len()),
@@ -196,38 +71,105 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use battle_tested::TokenType;
 
     #[test]
     fn test_basic_parsing() {
         let input = "test input".to_string();
         let parser = BattleTestedParser::new(input.clone());
         let result = parser.parse(&input);
-        
+
         assert!(result.is_ok());
         assert_eq!(result.unwrap().len(), 2);
     }
 
+    #[test]
+    fn test_battle_tested_parser_reports_character_columns() {
+        let input = "    if  x".to_string();
+        let tokens = BattleTestedParser::new(input.clone())
+            .parse(&input)
+            .unwrap();
+
+        assert_eq!(tokens[0].column, 5);
+        assert_eq!(tokens[1].column, 9);
+    }
+
+    #[test]
+    fn test_battle_tested_parser_uses_shared_operator_table() {
+        let input = "a >= b && c".to_string();
+        let tokens = BattleTestedParser::new(input.clone())
+            .parse(&input)
+            .unwrap();
+
+        assert_eq!(tokens[1].value, ">=");
+        assert_eq!(tokens[3].value, "&&");
+        assert!(tokens
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .all(|t| t.kind == TokenType::Operator));
+        assert!(battle_tested::DEFAULT_OPERATORS.contains(&"&&"));
+    }
+
+    #[test]
+    fn test_battle_tested_parser_splits_glued_tokens() {
+        let input = "x+5 f(3.14)".to_string();
+        let tokens = BattleTestedParser::new(input.clone())
+            .parse(&input)
+            .unwrap();
+        let lexed: Vec<(&str, usize)> = tokens
+            .iter()
+            .map(|t| (t.value.as_str(), t.column))
+            .collect();
+
+        assert_eq!(
+            lexed,
+            [
+                ("x", 1),
+                ("+", 2),
+                ("5", 3),
+                ("f", 5),
+                ("(", 6),
+                ("3.14", 7),
+                (")", 11)
+            ]
+        );
+        assert_eq!(tokens[1].kind, TokenType::Operator);
+        assert_eq!(tokens[5].kind, TokenType::Literal);
+    }
+
+    #[test]
+    fn test_parse_with_metrics() {
+        let input = "let total = a + 42".to_string();
+        let parser = BattleTestedParser::new(input.clone());
+        let (tokens, metrics) = parser.parse_with_metrics().unwrap();
+
+        assert_eq!(metrics.token_count, tokens.len());
+        assert_eq!(metrics.token_count, 6);
+        assert_eq!(metrics.byte_count, input.len());
+    }
+
     #[test]
     fn test_performance_characteristics() {
         let long_input = "a ".repeat(1000);
         let parser = BattleTestedParser::new(long_input.clone());
-        
+
         let start = Instant::now();
         let result = parser.parse(&long_input);
         let duration = start.elapsed();
-        
+
         assert!(result.is_ok());
         assert!(duration.as_millis() < 10, "Parsing took too long");
     }
-    
+
     #[test]
     fn test_minimal_parser_basic_parsing() {
         let parser = MinimalParser::new();
         let input = "hello world";
-        
+
         let result = parser.parse(input);
         assert!(result.is_ok());
-        
+
         let tokens = result.unwrap();
         assert_eq!(tokens.len(), 2);
         assert_eq!(tokens[0].value, "hello");
@@ -237,7 +179,7 @@ mod tests {
     #[test]
     fn test_token_classification() {
         let parser = MinimalParser::new();
-        
+
         assert_eq!(parser.classify_token("+"), TokenType::Operator);
         assert_eq!(parser.classify_token("if"), TokenType::Keyword);
         assert_eq!(parser.classify_token("variable"), TokenType::Identifier);